@@ -0,0 +1,211 @@
+//! How much memory a large scan takes, next to a tree of full paths
+//!
+//! Run with `cargo bench --bench memory`. The synthetic tree looks like a
+//! volume full of JavaScript checkouts: a million empty files, with the same
+//! few names repeated in every package. It is written to a temporary
+//! directory first, which takes a while.
+//!
+//! The same directory is then read three ways: into a `DirEntry` tree that
+//! holds every full path, as scans were kept before `ScanTree`, and into a
+//! `ScanTree` by a full scan and by a live one.
+
+#![allow(dead_code)]
+
+#[path = "../src/model.rs"]
+mod model;
+#[path = "../src/policy.rs"]
+mod policy;
+#[path = "../src/scanner.rs"]
+mod scanner;
+#[path = "../src/tree.rs"]
+mod tree;
+
+use model::{DirEntry, SizeMode, Stat};
+use scanner::{scan_directory, CancelToken, ScanOptions, ScanProgress};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cmp::Reverse;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use tree::ScanTree;
+
+/// Counts the bytes currently allocated, and the most there have been
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let now = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(now, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    // Growing in place, as the system allocator can, holds no second copy
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let before = ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        PEAK.fetch_max(before + new_size - layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const PROJECTS: usize = 25;
+const PACKAGES: usize = 1000;
+/// Files directly in each package, besides those under `lib`
+const PACKAGE_FILES: &[&str] = &["index.js", "package.json", "README.md", "LICENSE"];
+const LIB_FILES: usize = 36;
+
+fn write_synthetic_tree(root: &Path) {
+    for project in 0..PROJECTS {
+        let modules = root.join(format!("project-{:03}/node_modules", project));
+        for package in 0..PACKAGES {
+            let package = modules.join(format!("package-{:03}", package));
+            fs::create_dir_all(package.join("lib")).expect("create package");
+            for name in PACKAGE_FILES {
+                fs::write(package.join(name), "").expect("write file");
+            }
+            for i in 0..LIB_FILES {
+                fs::write(package.join(format!("lib/module-{}.js", i)), "").expect("write file");
+            }
+        }
+    }
+}
+
+/// Scan `root` the way the window does, folding streamed subtrees into one
+/// tree as they arrive when `live` is set
+fn scan(root: &Path, live: bool) -> ScanTree {
+    let options = ScanOptions {
+        live,
+        ..Default::default()
+    };
+    let rx = scan_directory(root.to_path_buf(), options, CancelToken::new());
+    let mut streamed: Option<ScanTree> = None;
+    for progress in rx {
+        match progress {
+            ScanProgress::Subtree(subtree) => {
+                streamed
+                    .get_or_insert_with(|| ScanTree::from_entry(DirEntry::new_dir(root.into())))
+                    .insert_tree(subtree, SizeMode::Apparent);
+            }
+            ScanProgress::Complete(rest) => {
+                return match streamed {
+                    Some(mut tree) => {
+                        tree.absorb(rest, SizeMode::Apparent);
+                        tree
+                    }
+                    None => rest,
+                };
+            }
+            ScanProgress::Error(e) => panic!("scan failed: {}", e),
+            _ => {}
+        }
+    }
+    panic!("scan ended without a result");
+}
+
+/// Read `path` into a tree of `DirEntry` values, each with its full path
+fn scan_paths(path: &Path) -> DirEntry {
+    let metadata = fs::symlink_metadata(path).expect("read metadata");
+    if !metadata.is_dir() {
+        let mut entry =
+            DirEntry::new_file(path.to_path_buf(), metadata.len(), metadata.blocks() * 512);
+        entry.nlink = metadata.nlink();
+        entry.stat = Stat::from_metadata(&metadata);
+        return entry;
+    }
+    let mut entry = DirEntry::new_dir(path.to_path_buf());
+    entry.stat = Stat::from_metadata(&metadata);
+    for child in fs::read_dir(path).expect("read directory") {
+        let child = child.expect("read directory entry");
+        entry.children.push(scan_paths(&child.path()));
+    }
+    entry.children.sort_by_key(|c| Reverse(c.size));
+    entry.update_totals();
+    entry
+}
+
+/// Peak and kept bytes while building something, and how many items it holds
+struct Usage {
+    peak: usize,
+    kept: usize,
+    items: u64,
+}
+
+/// Measure what `build` allocates at most and what its result keeps
+fn measure<T>(build: impl FnOnce() -> T, items: impl FnOnce(&T) -> u64) -> Usage {
+    let base = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let start = Instant::now();
+    let result = build();
+    let elapsed = start.elapsed();
+    let usage = Usage {
+        peak: PEAK.load(Ordering::Relaxed) - base,
+        kept: ALLOCATED.load(Ordering::Relaxed) - base,
+        items: items(&result),
+    };
+    println!("  built {} items in {:.2?}", usage.items, elapsed);
+    drop(result);
+    usage
+}
+
+fn mib(bytes: usize) -> String {
+    format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn cell(bytes: usize, items: u64) -> String {
+    format!("{:>10} ({:>3} B/item)", mib(bytes), bytes as u64 / items)
+}
+
+fn tree_items(tree: &ScanTree) -> u64 {
+    tree.get(tree.root()).item_count()
+}
+
+fn main() {
+    let temp = tempfile::tempdir().expect("create temporary directory");
+    let start = Instant::now();
+    write_synthetic_tree(temp.path());
+    println!("tree written in {:.2?}\n", start.elapsed());
+    let root = temp.path();
+
+    println!("paths:");
+    let paths = measure(|| scan_paths(root), |entry| entry.files + entry.dirs);
+    println!("full scan:");
+    let full = measure(|| scan(root, false), tree_items);
+    println!("live scan:");
+    let live = measure(|| scan(root, true), tree_items);
+
+    println!("\n{:<10} {:>26} {:>26}", "", "peak", "kept");
+    let rows = [
+        ("paths", &paths),
+        ("full scan", &full),
+        ("live scan", &live),
+    ];
+    for (name, usage) in rows {
+        println!(
+            "{:<10} {:>26} {:>26}",
+            name,
+            cell(usage.peak, usage.items),
+            cell(usage.kept, usage.items)
+        );
+    }
+    for (name, usage) in &rows[1..] {
+        println!(
+            "{} against paths: {:.2}x the peak, {:.2}x kept",
+            name,
+            usage.peak as f64 / paths.peak as f64,
+            usage.kept as f64 / paths.kept as f64
+        );
+    }
+}
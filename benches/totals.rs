@@ -0,0 +1,153 @@
+//! What caching subtree totals saves over summing them on demand
+//!
+//! Run with `cargo bench --bench totals`. Each case is timed both ways: the
+//! way sizes used to be found, by walking the whole subtree every time, and
+//! with the totals every entry now carries.
+
+#![allow(dead_code)]
+
+#[path = "../src/model.rs"]
+mod model;
+#[path = "../src/policy.rs"]
+mod policy;
+#[path = "../src/tree.rs"]
+mod tree;
+
+use model::{DirEntry, SizeMode};
+use std::cmp::Reverse;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tree::ScanTree;
+
+/// Levels of nesting in the deep tree
+const DEPTH: usize = 300;
+/// Files beside the next directory at each level
+const FILES_PER_LEVEL: usize = 100;
+
+/// A deep tree, like a long chain of nested build directories
+fn deep_tree() -> DirEntry {
+    let mut path = PathBuf::from("/deep");
+    let mut dirs = Vec::new();
+    for _ in 0..DEPTH {
+        dirs.push(path.clone());
+        path.push("nested");
+    }
+
+    let mut entry: Option<DirEntry> = None;
+    for (level, dir) in dirs.into_iter().enumerate().rev() {
+        let mut current = DirEntry::new_dir(dir.clone());
+        for i in 0..FILES_PER_LEVEL {
+            let size = ((level * 7919 + i * 104_729) % 65_536) as u64;
+            let file = DirEntry::new_file(dir.join(format!("part-{}.o", i)), size, size);
+            current.children.push(file);
+        }
+        current.children.extend(entry.take());
+        current.update_totals();
+        entry = Some(current);
+    }
+    entry.expect("at least one level")
+}
+
+/// Total size found by walking the subtree, as it used to be
+fn walked_size(entry: &DirEntry) -> u64 {
+    if entry.is_file {
+        entry.size
+    } else {
+        entry.children.iter().map(walked_size).sum()
+    }
+}
+
+fn walked_count(entry: &DirEntry) -> u64 {
+    1 + entry.children.iter().map(walked_count).sum::<u64>()
+}
+
+fn sort_walking(entry: &mut DirEntry) {
+    entry.children.sort_by_key(|c| Reverse(walked_size(c)));
+    for child in &mut entry.children {
+        sort_walking(child);
+    }
+}
+
+/// Every directory's size and item count, as a report or chart needs them
+fn totals_walking(entry: &DirEntry, out: &mut Vec<(u64, u64)>) {
+    out.push((walked_size(entry), walked_count(entry)));
+    for child in entry.children.iter().filter(|c| !c.is_file) {
+        totals_walking(child, out);
+    }
+}
+
+fn totals_cached(entry: &DirEntry, out: &mut Vec<(u64, u64)>) {
+    out.push((entry.size, entry.files + entry.dirs));
+    for child in entry.children.iter().filter(|c| !c.is_file) {
+        totals_cached(child, out);
+    }
+}
+
+/// Average time of `run` over enough rounds to fill a quarter of a second
+fn time(mut run: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut rounds = 0;
+    while rounds == 0 || start.elapsed() < Duration::from_millis(250) {
+        run();
+        rounds += 1;
+    }
+    start.elapsed() / rounds
+}
+
+fn report(case: &str, walking: Duration, cached: Duration) {
+    let speedup = walking.as_secs_f64() / cached.as_secs_f64().max(1e-9);
+    println!(
+        "{:<28} {:>12.2?} {:>12.2?} {:>9.0}x",
+        case, walking, cached, speedup
+    );
+}
+
+fn main() {
+    let entry = deep_tree();
+    println!("{} levels, {} items\n", DEPTH, entry.files + entry.dirs);
+    println!(
+        "{:<28} {:>12} {:>12} {:>10}",
+        "", "walking", "cached", "speedup"
+    );
+
+    let mut sorted = entry.clone();
+    let walking = time(|| sort_walking(black_box(&mut sorted)));
+    let mut sorted = ScanTree::from_entry(entry.clone());
+    let cached = time(|| black_box(&mut sorted).sort_by_size(SizeMode::Apparent));
+    report("sort by size", walking, cached);
+
+    let walking = time(|| {
+        let mut out = Vec::new();
+        totals_walking(black_box(&entry), &mut out);
+        black_box(out);
+    });
+    let cached = time(|| {
+        let mut out = Vec::new();
+        totals_cached(black_box(&entry), &mut out);
+        black_box(out);
+    });
+    report("size and count of each dir", walking, cached);
+
+    // A file at the bottom changes, as seen while watching: every ancestor
+    // re-sorts its children by size
+    let mut tree = ScanTree::from_entry(entry.clone());
+    let deepest = (1..DEPTH).fold(PathBuf::from("/deep"), |path, _| path.join("nested"));
+    let file = deepest.join("part-0.o");
+    let mut walked = entry.clone();
+    let walking = time(|| {
+        let mut current = &mut walked;
+        loop {
+            current.children.sort_by_key(|c| Reverse(walked_size(c)));
+            match current.children.iter_mut().find(|c| !c.is_file) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+    });
+    let cached = time(|| {
+        let update = DirEntry::new_file(file.clone(), 4096, 4096);
+        tree.insert(black_box(update), SizeMode::Apparent);
+    });
+    report("update one file, 300 deep", walking, cached);
+}
@@ -1,11 +1,31 @@
-use crate::model::{is_protected_path, DirEntry};
-use std::fs;
-use std::path::PathBuf;
+use crate::model::is_protected_path;
+use crate::remove::{remove_tree, RemoveFailure};
+use crate::trash::{self, TrashItem};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Where the privileged helper is installed; the polkit policy names this path
+pub const HELPER_PATH: &str = "/usr/lib/scorch/scorch-helper";
+
+/// How an entry should be deleted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Move to the freedesktop.org Trash, where it can be restored
+    Trash,
+    /// Unlink for good
+    Permanent,
+}
 
 /// Result of a delete operation
 #[derive(Debug)]
 pub enum DeleteResult {
     Success,
+    /// Moved to the trash; carries what's needed to restore it
+    Trashed(TrashItem),
+    /// Permanent delete left some things behind
+    Incomplete(Vec<RemoveFailure>),
     ProtectedPath,
     NotFound,
     PermissionDenied(String),
@@ -13,7 +33,7 @@ pub enum DeleteResult {
 }
 
 /// Delete a file or directory
-pub fn delete_entry(path: &PathBuf) -> DeleteResult {
+pub fn delete_entry(path: &Path, mode: DeleteMode) -> DeleteResult {
     // Check if protected
     if is_protected_path(path) {
         return DeleteResult::ProtectedPath;
@@ -25,14 +45,20 @@ pub fn delete_entry(path: &PathBuf) -> DeleteResult {
     }
 
     // Attempt deletion
-    let result = if path.is_dir() {
-        fs::remove_dir_all(path)
-    } else {
-        fs::remove_file(path)
+    let result = match mode {
+        DeleteMode::Trash => trash::trash(path).map(DeleteResult::Trashed),
+        DeleteMode::Permanent => {
+            let failures = remove_tree(path);
+            if failures.is_empty() {
+                Ok(DeleteResult::Success)
+            } else {
+                Ok(DeleteResult::Incomplete(failures))
+            }
+        }
     };
 
     match result {
-        Ok(_) => DeleteResult::Success,
+        Ok(deleted) => deleted,
         Err(e) => {
             if e.kind() == std::io::ErrorKind::PermissionDenied {
                 DeleteResult::PermissionDenied(e.to_string())
@@ -43,9 +69,64 @@ pub fn delete_entry(path: &PathBuf) -> DeleteResult {
     }
 }
 
-/// Get info about what will be deleted
-pub fn get_delete_info(entry: &DirEntry) -> (usize, u64) {
-    let count = entry.item_count();
-    let size = entry.total_size();
-    (count, size)
+/// Whether deletes can be retried as administrator
+pub fn helper_available() -> bool {
+    Path::new(HELPER_PATH).is_file()
+}
+
+/// Delete permanently as root, through pkexec and the privileged helper
+///
+/// Blocks while polkit asks for a password, so keep it off the UI thread.
+pub fn delete_as_admin(path: &Path) -> DeleteResult {
+    if is_protected_path(path) {
+        return DeleteResult::ProtectedPath;
+    }
+
+    let output = match Command::new("pkexec")
+        .arg(HELPER_PATH)
+        .arg("delete")
+        .arg("--")
+        .arg(path)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => return DeleteResult::Error(format!("Cannot run pkexec: {}", e)),
+    };
+    match output.status.code() {
+        Some(0) => return DeleteResult::Success,
+        Some(1) => {}
+        // pkexec's own codes for a dismissed or impossible authentication
+        Some(126) => return DeleteResult::Error("Authorization was refused".to_string()),
+        Some(127) => return DeleteResult::Error("Could not authenticate".to_string()),
+        _ => {
+            return DeleteResult::Error(format!(
+                "The helper failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    // One KIND\tMESSAGE\tPATH record per thing left behind
+    let mut failures = Vec::new();
+    for record in output.stdout.split(|&b| b == 0).filter(|r| !r.is_empty()) {
+        let mut fields = record.splitn(3, |&b| b == b'\t');
+        let (Some(kind), Some(message), Some(failed)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        match kind {
+            b"protected" => return DeleteResult::ProtectedPath,
+            b"missing" => return DeleteResult::NotFound,
+            _ => failures.push(RemoveFailure {
+                path: PathBuf::from(OsStr::from_bytes(failed)),
+                message: String::from_utf8_lossy(message).into_owned(),
+                permission_denied: kind == b"denied",
+            }),
+        }
+    }
+    if failures.is_empty() {
+        return DeleteResult::Error("The helper failed without saying why".to_string());
+    }
+    DeleteResult::Incomplete(failures)
 }
@@ -1,14 +1,31 @@
-use crate::model::DirEntry;
-use crate::sunburst::Segment;
+use crate::diff::{diff_trees, DeltaEntry};
+use crate::model::{DirEntry, SizeMode};
+use crate::owners::{owner_usage, OwnerNames, OwnerUsage};
+use crate::scanner::{CancelToken, ScanOptions};
+use crate::sunburst::{ColorMode, Segment, OWNER_COLORS};
+use crate::trash::{self, TrashItem};
+use crate::tree::{Node, ScanTree};
+use crate::watch::WatchEvent;
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
+/// How many trashed entries Ctrl+Z can bring back
+const MAX_UNDO: usize = 20;
+
+/// A trashed entry and the subtree it was removed from the scan with
+#[derive(Debug, Clone)]
+pub struct UndoEntry {
+    pub item: TrashItem,
+    pub entry: DirEntry,
+}
+
 /// Application state
 #[derive(Debug, Clone)]
 pub struct AppState {
-    /// Root of the scanned tree
-    pub scan_root: Option<DirEntry>,
+    /// The scanned tree
+    pub scan_root: Option<ScanTree>,
     /// Current view root (for zooming)
     pub view_root: PathBuf,
     /// Currently hovered segment
@@ -17,10 +34,32 @@ pub struct AppState {
     pub segments: Vec<Segment>,
     /// Is scanning in progress
     pub scanning: bool,
-    /// Scan progress message
-    pub progress_msg: String,
+    /// Aborts the running scan
+    pub scan_cancel: Option<CancelToken>,
     /// Items scanned count
     pub items_scanned: usize,
+    /// Which size drives the sunburst
+    pub size_mode: SizeMode,
+    /// Options for the next scan
+    pub scan_options: ScanOptions,
+    /// Earlier scan to compare against
+    pub baseline: Option<ScanTree>,
+    /// Changes from the baseline to the current scan
+    pub diff: Option<DeltaEntry>,
+    /// What segment colours represent
+    pub color_mode: ColorMode,
+    /// Usage by owner within the current view, kept while colouring by owner
+    pub view_owners: Option<OwnerUsage>,
+    /// User and group names for showing owners
+    pub owner_names: OwnerNames,
+    /// Recently trashed entries, most recent last
+    pub undo_stack: Vec<UndoEntry>,
+    /// Entries marked for batch deletion
+    pub marked: BTreeSet<PathBuf>,
+    /// Stops watching the tree for changes
+    pub watch_cancel: Option<CancelToken>,
+    /// Aborts background refreshes of the current tree
+    pub refresh_cancel: CancelToken,
 }
 
 impl Default for AppState {
@@ -31,8 +70,22 @@ impl Default for AppState {
             hover_path: None,
             segments: Vec::new(),
             scanning: false,
-            progress_msg: String::new(),
+            scan_cancel: None,
             items_scanned: 0,
+            size_mode: SizeMode::default(),
+            scan_options: ScanOptions {
+                live: true,
+                ..ScanOptions::default()
+            },
+            baseline: None,
+            diff: None,
+            color_mode: ColorMode::default(),
+            view_owners: None,
+            owner_names: OwnerNames::load(),
+            undo_stack: Vec::new(),
+            marked: BTreeSet::new(),
+            watch_cancel: None,
+            refresh_cancel: CancelToken::new(),
         }
     }
 }
@@ -43,15 +96,24 @@ impl AppState {
     }
 
     /// Get current view entry from scan root
-    pub fn get_view_entry(&self) -> Option<&DirEntry> {
-        self.scan_root
-            .as_ref()
-            .and_then(|root| root.find_by_path(&self.view_root))
+    pub fn get_view_entry(&self) -> Option<&Node> {
+        let tree = self.scan_root.as_ref()?;
+        tree.find(&self.view_root).map(|id| tree.get(id))
+    }
+
+    /// Look up any entry of the scanned tree by path
+    pub fn find_entry(&self, path: &Path) -> Option<&Node> {
+        let tree = self.scan_root.as_ref()?;
+        tree.find(path).map(|id| tree.get(id))
     }
 
     /// Navigate to a subdirectory
     pub fn navigate_to(&mut self, path: PathBuf) {
-        if self.scan_root.as_ref().and_then(|r| r.find_by_path(&path)).is_some() {
+        if self
+            .scan_root
+            .as_ref()
+            .is_some_and(|tree| tree.contains(&path))
+        {
             self.view_root = path;
             self.rebuild_segments();
         }
@@ -62,7 +124,11 @@ impl AppState {
         if let Some(parent) = self.view_root.parent() {
             let parent = parent.to_path_buf();
             // Only navigate up if we can find the parent in our tree
-            if self.scan_root.as_ref().and_then(|r| r.find_by_path(&parent)).is_some() {
+            if self
+                .scan_root
+                .as_ref()
+                .is_some_and(|tree| tree.contains(&parent))
+            {
                 self.view_root = parent;
                 self.rebuild_segments();
             }
@@ -72,7 +138,7 @@ impl AppState {
     /// Check if we can navigate up
     pub fn can_navigate_up(&self) -> bool {
         if let Some(root) = &self.scan_root {
-            self.view_root != root.path && self.view_root.parent().is_some()
+            self.view_root != root.root_path() && self.view_root.parent().is_some()
         } else {
             false
         }
@@ -80,9 +146,285 @@ impl AppState {
 
     /// Rebuild segments from current view
     pub fn rebuild_segments(&mut self) {
-        if let Some(entry) = self.get_view_entry() {
-            self.segments = crate::sunburst::build_segments(entry, crate::sunburst::MAX_DEPTH);
+        if let Some(tree) = &self.scan_root {
+            if let Some(id) = tree.find(&self.view_root) {
+                self.segments = crate::sunburst::build_segments(
+                    tree,
+                    id,
+                    crate::sunburst::MAX_DEPTH,
+                    self.size_mode,
+                );
+            }
+        }
+        if let Some(diff) = &self.diff {
+            for segment in &mut self.segments {
+                segment.change = diff.find(&segment.path).map(|d| (d.change, d.delta()));
+            }
+        }
+        if !self.marked.is_empty() {
+            for segment in &mut self.segments {
+                segment.marked = self.marked.iter().any(|m| segment.path.starts_with(m));
+            }
+        }
+        self.view_owners = None;
+        if self.color_mode == ColorMode::Owner {
+            self.color_by_owner();
+        }
+    }
+
+    /// Give each of the largest owners in view a colour of their own
+    fn color_by_owner(&mut self) {
+        let Some(tree) = &self.scan_root else {
+            return;
+        };
+        let Some(id) = tree.find(&self.view_root) else {
+            return;
+        };
+        let usage = owner_usage(tree, id, self.size_mode);
+        let coloured = &usage.users[..usage.users.len().min(OWNER_COLORS.len())];
+        for segment in &mut self.segments {
+            let uid = segment.stat.is_recorded().then_some(segment.stat.uid);
+            segment.owner = coloured.iter().position(|&(owner, _)| owner == uid);
+        }
+        self.view_owners = Some(usage);
+    }
+
+    /// Switch what segment colours represent
+    pub fn set_color_mode(&mut self, mode: ColorMode) {
+        self.color_mode = mode;
+        self.rebuild_segments();
+    }
+
+    /// Recompute the changes since the baseline, if there is one
+    pub fn refresh_diff(&mut self) {
+        self.diff = match (&self.baseline, &self.scan_root) {
+            (Some(old), Some(new)) => Some(diff_trees(old, new, self.size_mode)),
+            _ => None,
+        };
+    }
+
+    /// Compare the current (and any later) scan against `baseline`
+    pub fn set_baseline(&mut self, baseline: ScanTree) {
+        self.baseline = Some(baseline);
+        self.refresh_diff();
+        self.rebuild_segments();
+    }
+
+    /// Switch between apparent and allocated sizes
+    pub fn set_size_mode(&mut self, mode: SizeMode) {
+        if self.size_mode == mode {
+            return;
+        }
+        self.size_mode = mode;
+        if let Some(root) = &mut self.scan_root {
+            root.sort_by_size(mode);
+        }
+        self.refresh_diff();
+        self.rebuild_segments();
+    }
+
+    /// Replace the scanned tree, keeping the current view if it still exists
+    pub fn set_scan_root(&mut self, mut tree: ScanTree) {
+        if self.size_mode != SizeMode::Apparent {
+            tree.sort_by_size(self.size_mode);
+        }
+        if !tree.contains(&self.view_root) {
+            self.view_root = tree.root_path().to_path_buf();
+        }
+        self.marked.retain(|m| tree.contains(m));
+        self.scan_root = Some(tree);
+        self.refresh_diff();
+        self.rebuild_segments();
+    }
+
+    /// Abandon refreshes still running, whose results belong to a tree that's
+    /// about to be replaced
+    pub fn cancel_refreshes(&mut self) {
+        self.refresh_cancel.cancel();
+        self.refresh_cancel = CancelToken::new();
+    }
+
+    /// Splice a finished top-level directory into the in-progress scan tree
+    pub fn add_scanned_subtree(&mut self, root: &Path, subtree: ScanTree) {
+        let mode = self.size_mode;
+        self.scan_root
+            .get_or_insert_with(|| ScanTree::from_entry(DirEntry::new_dir(root.to_path_buf())))
+            .insert_tree(subtree, mode);
+
+        if self.get_view_entry().is_none() {
+            self.view_root = root.to_path_buf();
+        }
+    }
+
+    /// Install what a live scan sent last, joining it to the subtrees that
+    /// streamed in before it
+    pub fn finish_scan(&mut self, rest: ScanTree) {
+        let tree = match self.scan_root.take() {
+            Some(mut streamed) if streamed.root_path() == rest.root_path() => {
+                streamed.absorb(rest, self.size_mode);
+                streamed
+            }
+            _ => rest,
+        };
+        self.set_scan_root(tree);
+    }
+
+    /// Swap a fresh scan of one entry into the tree, keeping ancestor sizes in step
+    pub fn replace_subtree(&mut self, mut subtree: ScanTree) {
+        let mode = self.size_mode;
+        let Some(tree) = &mut self.scan_root else {
+            return;
+        };
+        if subtree.root_path() == tree.root_path() {
+            self.set_scan_root(subtree);
+            return;
+        }
+
+        keep_counted_elsewhere(tree, &mut subtree);
+        tree.insert_tree(subtree, mode);
+        self.forget_missing();
+        self.refresh_diff();
+        self.rebuild_segments();
+    }
+
+    /// Apply changes seen on disk while watching
+    pub fn apply_watch_events(&mut self, events: Vec<WatchEvent>) {
+        let mode = self.size_mode;
+        let Some(tree) = &mut self.scan_root else {
+            return;
+        };
+        for event in events {
+            match event {
+                // Changes to the root arrive as changes inside it
+                WatchEvent::Updated(subtree) if subtree.root_path() == tree.root_path() => {}
+                WatchEvent::Updated(mut subtree) => {
+                    keep_counted_elsewhere(tree, &mut subtree);
+                    tree.insert_tree(subtree, mode);
+                }
+                WatchEvent::Removed(path) => {
+                    tree.remove(&path, mode);
+                }
+                WatchEvent::Degraded(_) | WatchEvent::Error(_) => {}
+            }
         }
+        self.forget_missing();
+        self.refresh_diff();
+        self.rebuild_segments();
+    }
+
+    /// Drop marks on entries that left the tree, and back the view out of them
+    fn forget_missing(&mut self) {
+        let Some(tree) = &self.scan_root else {
+            return;
+        };
+        self.marked.retain(|m| tree.contains(m));
+        while !tree.contains(&self.view_root) {
+            match self.view_root.parent() {
+                Some(parent) => self.view_root = parent.to_path_buf(),
+                None => {
+                    self.view_root = tree.root_path().to_path_buf();
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Drop a deleted entry from the scan tree, returning its subtree
+    pub fn remove_entry(&mut self, path: &Path) -> Option<DirEntry> {
+        let mode = self.size_mode;
+        let removed = self.scan_root.as_mut()?.remove(path, mode);
+        self.marked.retain(|m| !m.starts_with(path));
+        self.refresh_diff();
+        self.rebuild_segments();
+        removed
+    }
+
+    /// After a partial delete, drop whatever under `path` is really gone
+    ///
+    /// Returns the bytes that were freed.
+    pub fn prune_deleted(&mut self, path: &Path) -> u64 {
+        let mode = self.size_mode;
+        let Some(tree) = &mut self.scan_root else {
+            return 0;
+        };
+        let Some(mut entry) = tree.remove(path, mode) else {
+            return 0;
+        };
+        let before = entry.size_in(mode);
+        let freed = if entry.prune_missing() {
+            let after = entry.size_in(mode);
+            tree.insert(entry, mode);
+            before.saturating_sub(after)
+        } else {
+            before
+        };
+        self.marked.retain(|m| tree.contains(m));
+        self.refresh_diff();
+        self.rebuild_segments();
+        freed
+    }
+
+    /// Mark or unmark an entry for batch deletion
+    pub fn toggle_mark(&mut self, path: PathBuf) {
+        if !self.marked.remove(&path) {
+            self.marked.insert(path);
+        }
+        self.rebuild_segments();
+    }
+
+    /// Marked entries that aren't inside another marked entry
+    pub fn marked_roots(&self) -> Vec<PathBuf> {
+        // Sorted by component, so everything under a path directly follows it
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for path in &self.marked {
+            if !roots.last().is_some_and(|root| path.starts_with(root)) {
+                roots.push(path.clone());
+            }
+        }
+        roots
+    }
+
+    /// Bytes that deleting every marked entry would free
+    pub fn reclaimable(&self) -> u64 {
+        self.marked_roots()
+            .iter()
+            .filter_map(|path| self.find_entry(path))
+            .map(|entry| entry.size_in(self.size_mode))
+            .sum()
+    }
+
+    /// Remember a trashed entry so it can be restored
+    pub fn push_undo(&mut self, item: TrashItem, entry: DirEntry) {
+        if self.undo_stack.len() == MAX_UNDO {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(UndoEntry { item, entry });
+    }
+
+    /// Restore the most recently trashed entry on disk and in the tree
+    ///
+    /// Returns the restored path, or `None` if there is nothing to undo.
+    pub fn undo(&mut self) -> Option<Result<PathBuf, String>> {
+        let UndoEntry { item, entry } = self.undo_stack.pop()?;
+        if let Err(e) = trash::restore(&item) {
+            return Some(Err(format!(
+                "Cannot restore {}: {} (it is still in the Trash at {})",
+                item.original_path.display(),
+                e,
+                item.trashed_path.display()
+            )));
+        }
+
+        // The tree may have been replaced by a rescan that never saw the entry
+        let mode = self.size_mode;
+        if let Some(tree) = &mut self.scan_root {
+            if !tree.contains(&entry.path) {
+                tree.insert(entry, mode);
+            }
+        }
+        self.refresh_diff();
+        self.rebuild_segments();
+        Some(Ok(item.original_path))
     }
 
     /// Get breadcrumb path components
@@ -99,7 +441,7 @@ impl AppState {
             crumbs.push((current.clone(), name));
 
             if let Some(root) = &self.scan_root {
-                if current == root.path {
+                if current == root.root_path() {
                     break;
                 }
             }
@@ -115,3 +457,74 @@ impl AppState {
         crumbs
     }
 }
+
+/// Zero the hard links in a rescanned `subtree` that `tree` counted under
+/// another path
+///
+/// A rescan only knows the links inside what it rescanned, so without this
+/// it would count those inodes a second time.
+fn keep_counted_elsewhere(tree: &ScanTree, subtree: &mut ScanTree) {
+    let mut counted_elsewhere = Vec::new();
+    let mut stack = vec![subtree.root()];
+    while let Some(id) = stack.pop() {
+        let node = subtree.get(id);
+        if node.is_hard_linked() && node.inodes > 0 {
+            let old = tree.find(&subtree.path(id)).map(|old| tree.get(old));
+            if old.is_some_and(|old| old.is_file && old.inodes == 0) {
+                counted_elsewhere.push(id);
+            }
+        }
+        stack.extend(&node.children);
+    }
+    for id in counted_elsewhere {
+        subtree.count_elsewhere(id, SizeMode::Apparent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linked_file(path: &str, counted: bool) -> DirEntry {
+        let mut file = DirEntry::new_file(PathBuf::from(path), 100, 4096);
+        file.nlink = 2;
+        if !counted {
+            (file.size, file.disk_size, file.inodes) = (0, 0, 0);
+        }
+        file
+    }
+
+    fn dir(path: &str, children: Vec<DirEntry>) -> DirEntry {
+        let mut dir = DirEntry::new_dir(PathBuf::from(path));
+        dir.children = children;
+        dir.update_totals();
+        dir
+    }
+
+    #[test]
+    fn rescans_keep_links_counted_elsewhere_at_zero() {
+        // /r/a/link was counted; /r/b/link shares its inode and wasn't
+        let tree = ScanTree::from_entry(dir(
+            "/r",
+            vec![
+                dir("/r/a", vec![linked_file("/r/a/link", true)]),
+                dir("/r/b", vec![linked_file("/r/b/link", false)]),
+            ],
+        ));
+
+        // A rescan of /r/b alone sees its link for the first time
+        let rescan = |path: &str, link: &str| {
+            let mut rescanned = ScanTree::from_entry(dir(path, vec![linked_file(link, true)]));
+            keep_counted_elsewhere(&tree, &mut rescanned);
+            rescanned
+        };
+        let rescanned = rescan("/r/b", "/r/b/link");
+        let root = rescanned.get(rescanned.root());
+        assert_eq!((root.size, root.inodes), (0, 1));
+        assert_eq!(rescanned.get(root.children[0]).disk_size, 0);
+
+        let rescanned = rescan("/r/a", "/r/a/link");
+        let root = rescanned.get(rescanned.root());
+        assert_eq!((root.size, root.inodes), (100, 2));
+    }
+}
@@ -0,0 +1,188 @@
+//! Deletes paths as root on behalf of scorch, run through pkexec
+//!
+//! Usage: `scorch-helper delete -- PATH...`
+//!
+//! Protected paths are checked again here instead of trusting the caller,
+//! against the path with its parent's symlinks resolved.
+//! Only the built-in rules and the system config count: the environment and
+//! the user's own config belong to whoever ran pkexec.
+//!
+//! Anything not fully removed is reported on stdout as `KIND\tMESSAGE\tPATH\0`,
+//! where KIND is `protected`, `missing`, `denied` or `failed`. The exit code
+//! is 0 when everything went, 1 when something didn't and 2 on bad usage.
+
+#[allow(dead_code)]
+#[path = "../policy.rs"]
+mod policy;
+#[allow(dead_code)]
+#[path = "../remove.rs"]
+mod remove;
+
+use policy::{parse_config, Policy, SYSTEM_CONFIG};
+use remove::Dir;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: scorch-helper delete -- PATH...";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args_os().skip(1);
+    if args.next().as_deref() != Some(OsStr::new("delete")) {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let paths: Vec<PathBuf> = args
+        .filter(|arg| arg.as_os_str() != "--")
+        .map(PathBuf::from)
+        .collect();
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+
+    let policy = system_policy();
+    let mut out = io::stdout().lock();
+    let mut complete = true;
+    for path in &paths {
+        for (kind, message, failed) in delete(&policy, path) {
+            complete = false;
+            let written = write!(out, "{}\t{}\t", kind, message.replace(['\t', '\0'], " "))
+                .and_then(|_| out.write_all(failed.as_os_str().as_bytes()))
+                .and_then(|_| out.write_all(b"\0"));
+            if written.is_err() {
+                return ExitCode::from(1);
+            }
+        }
+    }
+    let _ = out.flush();
+
+    if complete {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
+
+/// The built-in rules plus the system config, ignoring anything per-user
+fn system_policy() -> Policy {
+    let mut policy = Policy::defaults();
+    if let Ok(text) = fs::read_to_string(SYSTEM_CONFIG) {
+        let (rules, errors) = parse_config(&text, None);
+        for error in errors {
+            eprintln!("scorch-helper: {}: {}", SYSTEM_CONFIG, error);
+        }
+        policy.rules.extend(rules);
+    }
+    policy
+}
+
+/// Remove one path, returning what stayed behind as (kind, message, path)
+fn delete(policy: &Policy, path: &Path) -> Vec<(&'static str, String, PathBuf)> {
+    // pkexec doesn't keep the caller's working directory
+    if !path.is_absolute() {
+        let message = "not an absolute path".to_string();
+        return vec![("failed", message, path.to_path_buf())];
+    }
+    if let Some(rule) = policy.protecting_rule(path) {
+        let message = format!("protected by {}", rule.source);
+        return vec![("protected", message, path.to_path_buf())];
+    }
+    if let Err(e) = fs::symlink_metadata(path) {
+        let kind = if e.kind() == io::ErrorKind::NotFound {
+            "missing"
+        } else {
+            "failed"
+        };
+        return vec![(kind, e.to_string(), path.to_path_buf())];
+    }
+
+    // Resolve the parent once, judge that, and delete relative to the
+    // directory it names from then on, so a symlink swapped into the path
+    // can't point the delete somewhere the policy never saw
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        let message = "not a removable path".to_string();
+        return vec![("failed", message, path.to_path_buf())];
+    };
+    let real_parent = match fs::canonicalize(parent) {
+        Ok(real_parent) => real_parent,
+        Err(e) => return vec![("failed", e.to_string(), path.to_path_buf())],
+    };
+    if let Some(rule) = policy.protecting_rule(&real_parent.join(name)) {
+        let message = format!("protected by {}", rule.source);
+        return vec![("protected", message, path.to_path_buf())];
+    }
+    let dir = match Dir::open(&real_parent) {
+        Ok(dir) => dir,
+        Err(e) => return vec![("failed", e.to_string(), path.to_path_buf())],
+    };
+    match fs::read_link(format!("/proc/self/fd/{}", dir.as_raw_fd())) {
+        Ok(opened) if opened == real_parent => {}
+        _ => {
+            let message = "moved while being deleted".to_string();
+            return vec![("failed", message, path.to_path_buf())];
+        }
+    }
+
+    remove::remove_in(&dir, name, path)
+        .into_iter()
+        .map(|failure| {
+            let kind = if failure.permission_denied {
+                "denied"
+            } else {
+                "failed"
+            };
+            (kind, failure.message, failure.path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use policy::Rule;
+    use std::os::unix::fs::symlink;
+
+    /// A temp dir holding `guarded/keep.txt`, with a policy protecting `guarded`
+    fn setup() -> (tempfile::TempDir, PathBuf, Policy) {
+        let temp = tempfile::tempdir().unwrap();
+        let base = fs::canonicalize(temp.path()).unwrap();
+        let guarded = base.join("guarded");
+        fs::create_dir(&guarded).unwrap();
+        fs::write(guarded.join("keep.txt"), "x").unwrap();
+        let pattern = format!("{}/**", guarded.display());
+        let policy = Policy {
+            rules: vec![Rule::parse(&pattern, None).unwrap()],
+        };
+        (temp, base, policy)
+    }
+
+    #[test]
+    fn refuses_protected_paths_reached_through_symlinks() {
+        let (_temp, base, policy) = setup();
+        symlink(base.join("guarded"), base.join("alias")).unwrap();
+
+        let left = delete(&policy, &base.join("alias/keep.txt"));
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].0, "protected");
+        assert!(base.join("guarded/keep.txt").exists());
+    }
+
+    #[test]
+    fn removes_symlinks_without_following_them() {
+        let (_temp, base, policy) = setup();
+        symlink(base.join("guarded"), base.join("link")).unwrap();
+        fs::create_dir(base.join("tree")).unwrap();
+        symlink(base.join("guarded"), base.join("tree/link")).unwrap();
+
+        assert!(delete(&policy, &base.join("link")).is_empty());
+        assert!(delete(&policy, &base.join("tree")).is_empty());
+        assert!(fs::symlink_metadata(base.join("link")).is_err());
+        assert!(!base.join("tree").exists());
+        assert!(base.join("guarded/keep.txt").exists());
+    }
+}
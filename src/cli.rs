@@ -0,0 +1,670 @@
+use crate::actions::{delete_entry, DeleteMode, DeleteResult};
+use crate::diff::{diff_trees, format_delta, Change, DeltaEntry};
+use crate::model::{format_count, format_size, format_timestamp, SizeMode};
+use crate::owners::{owner_usage, OwnerNames, Usage};
+use crate::plan::{self, PlanEntry};
+use crate::policy;
+use crate::scanner::{scan_directory, CancelToken, ScanOptions, ScanProgress};
+use crate::snapshot::{self, SnapshotInfo};
+use crate::tree::{NodeId, ScanTree};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Default number of children listed per directory
+const DEFAULT_TOP: usize = 20;
+
+/// Default number of directory levels printed below the root
+const DEFAULT_DEPTH: usize = 2;
+
+const USAGE: &str = "\
+Usage:
+  scorch                          Launch the GUI
+  scorch scan <PATH> [OPTIONS]    Scan PATH and print a size report
+  scorch load <FILE> [OPTIONS]    Print the size report of a saved snapshot
+  scorch diff <OLD> <NEW> [OPTIONS]
+                                  Show what grew between two snapshots or
+                                  directories (a directory is scanned now)
+  scorch owners <PATH|FILE> [OPTIONS]
+                                  Total usage by user and group for a
+                                  directory (scanned now) or a snapshot
+  scorch exec-plan <PLAN> [OPTIONS]
+                                  Delete the entries of a plan exported from
+                                  the GUI, skipping any that changed since
+
+Options:
+  --top <N>      Show the N largest entries per directory, or owners
+                 per table (default 20)
+  --depth <N>    Descend N directory levels below PATH (default 2)
+  --threads <N>  Scan with N threads (default: one per CPU, 1 = serial)
+  --allocated    Rank by size allocated on disk instead of apparent size
+  --inodes       Rank by inodes used instead of bytes
+  --count-links  Count every hard link in full instead of once per inode
+  -x, --one-file-system
+                 Don't descend into directories on other filesystems
+  --save <FILE>  Also save the scan as a snapshot for later browsing
+  --dry-run      Check every entry of a plan but delete nothing
+  --permanent    Delete plan entries for good instead of moving them to
+                 the Trash
+  -h, --help     Print this help";
+
+/// Parsed command line options shared by all commands
+#[derive(Debug, Clone)]
+struct Args {
+    paths: Vec<PathBuf>,
+    top: usize,
+    depth: usize,
+    /// Explicit size mode; defaults to apparent (or the snapshot's mode)
+    mode: Option<SizeMode>,
+    options: ScanOptions,
+    save: Option<PathBuf>,
+    dry_run: bool,
+    permanent: bool,
+}
+
+impl Args {
+    /// The single PATH/FILE argument of a command
+    fn single_path(&self, command: &str, what: &str) -> Result<&PathBuf, String> {
+        match self.paths.as_slice() {
+            [path] => Ok(path),
+            [] => Err(format!("{} requires a {}", command, what)),
+            [_, extra, ..] => Err(format!("unexpected argument '{}'", extra.display())),
+        }
+    }
+}
+
+/// Run the command line interface, returning the process exit code
+pub fn run(args: &[String]) -> i32 {
+    let command = match args.first().map(String::as_str) {
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Some(command) => command,
+        None => return usage_error("missing command"),
+    };
+
+    let parsed = match parse_args(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(e) => return usage_error(&e),
+    };
+
+    let result = match command {
+        "scan" => parsed
+            .single_path("scan", "PATH")
+            .map(|root| run_scan(root, &parsed)),
+        "load" => parsed
+            .single_path("load", "FILE")
+            .map(|file| run_load(file, &parsed)),
+        "diff" => match parsed.paths.as_slice() {
+            [old, new] => Ok(run_diff(old, new, &parsed)),
+            _ => Err("diff requires an OLD and a NEW snapshot or directory".to_string()),
+        },
+        "owners" => parsed
+            .single_path("owners", "PATH or FILE")
+            .map(|path| run_owners(path, &parsed)),
+        "exec-plan" => parsed
+            .single_path("exec-plan", "PLAN")
+            .map(|file| run_exec_plan(file, &parsed)),
+        other => Err(format!("unknown command '{}'", other)),
+    };
+    result.unwrap_or_else(|e| usage_error(&e))
+}
+
+fn usage_error(msg: &str) -> i32 {
+    eprintln!("scorch: {}\n\n{}", msg, USAGE);
+    2
+}
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut parsed = Args {
+        paths: Vec::new(),
+        top: DEFAULT_TOP,
+        depth: DEFAULT_DEPTH,
+        mode: None,
+        options: ScanOptions::default(),
+        save: None,
+        dry_run: false,
+        permanent: false,
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--top" => parsed.top = parse_number(arg, iter.next())?,
+            "--depth" => parsed.depth = parse_number(arg, iter.next())?,
+            "--threads" => parsed.options.threads = parse_number(arg, iter.next())?,
+            "--allocated" => parsed.mode = Some(SizeMode::Allocated),
+            "--inodes" => parsed.mode = Some(SizeMode::Inodes),
+            "--count-links" => parsed.options.count_hard_links = true,
+            "-x" | "--one-file-system" => parsed.options.one_file_system = true,
+            "--save" => {
+                let file = iter.next().ok_or("--save requires a FILE")?;
+                parsed.save = Some(PathBuf::from(file));
+            }
+            "--dry-run" => parsed.dry_run = true,
+            "--permanent" => parsed.permanent = true,
+            flag if flag.starts_with('-') => return Err(format!("unknown option '{}'", flag)),
+            path => parsed.paths.push(PathBuf::from(path)),
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn parse_number(flag: &str, value: Option<&String>) -> Result<usize, String> {
+    let value = value.ok_or_else(|| format!("{} requires a value", flag))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, flag))
+}
+
+/// Scan `root` to completion, showing progress on a terminal
+fn scan_tree(root: &Path, options: &ScanOptions) -> Result<ScanTree, String> {
+    let show_progress = io::stderr().is_terminal();
+    let rx = scan_directory(root.to_path_buf(), options.clone(), CancelToken::new());
+
+    let mut result = Err("scan ended unexpectedly".to_string());
+    for progress in rx {
+        match progress {
+            ScanProgress::Scanning(path) => {
+                if show_progress {
+                    eprint!("\rScorching {}...", path);
+                }
+            }
+            ScanProgress::ItemCount(count) => {
+                if show_progress {
+                    eprint!("\rScorching... {} items consumed", count);
+                }
+            }
+            ScanProgress::Subtree(_) => {
+                // Only sent in live mode, which the CLI doesn't use
+            }
+            ScanProgress::Complete(tree) => {
+                result = Ok(tree);
+                break;
+            }
+            ScanProgress::Cancelled(_) => {
+                result = Err("scan cancelled".to_string());
+                break;
+            }
+            ScanProgress::Error(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    if show_progress {
+        eprint!("\r\x1b[K");
+    }
+    result
+}
+
+fn run_scan(root: &Path, args: &Args) -> i32 {
+    let mut tree = match scan_tree(root, &args.options) {
+        Ok(tree) => tree,
+        Err(e) => {
+            eprintln!("scorch: {}", e);
+            return 1;
+        }
+    };
+
+    let mode = args.mode.unwrap_or_default();
+    if let Some(file) = &args.save {
+        let info = SnapshotInfo::new(tree.root_path().to_path_buf(), mode);
+        if let Err(e) = snapshot::save(file, &tree, &info) {
+            eprintln!("scorch: {}", e);
+            return 1;
+        }
+    }
+    if mode != SizeMode::Apparent {
+        tree.sort_by_size(mode);
+    }
+    print_report(&tree, args.top, args.depth, mode);
+    0
+}
+
+fn run_load(file: &Path, args: &Args) -> i32 {
+    let (info, mut tree) = match snapshot::load(file) {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            eprintln!("scorch: {}", e);
+            return 1;
+        }
+    };
+
+    println!(
+        "Snapshot of {} on {}, taken {}\n",
+        info.root.display(),
+        info.hostname,
+        format_timestamp(info.timestamp)
+    );
+    let mode = args.mode.unwrap_or(info.size_mode);
+    tree.sort_by_size(mode);
+    print_report(&tree, args.top, args.depth, mode);
+    0
+}
+
+/// Load a snapshot file, or scan a directory as it is now
+fn load_or_scan(
+    path: &Path,
+    options: &ScanOptions,
+) -> Result<(Option<SnapshotInfo>, ScanTree), String> {
+    if path.is_dir() {
+        scan_tree(path, options).map(|tree| (None, tree))
+    } else {
+        snapshot::load(path).map(|(info, tree)| (Some(info), tree))
+    }
+}
+
+/// One-line description of where a tree came from
+fn describe_source(path: &Path, info: Option<&SnapshotInfo>) -> String {
+    match info {
+        Some(info) => format!(
+            "snapshot of {} on {}, taken {}",
+            info.root.display(),
+            info.hostname,
+            format_timestamp(info.timestamp)
+        ),
+        None => format!("live scan of {}", path.display()),
+    }
+}
+
+fn run_diff(old_path: &Path, new_path: &Path, args: &Args) -> i32 {
+    let mut trees = Vec::new();
+    for path in [old_path, new_path] {
+        match load_or_scan(path, &args.options) {
+            Ok(tree) => trees.push(tree),
+            Err(e) => {
+                eprintln!("scorch: {}", e);
+                return 1;
+            }
+        }
+    }
+    let (new_info, new) = trees.pop().expect("two trees");
+    let (old_info, old) = trees.pop().expect("two trees");
+
+    // Compare in the old snapshot's terms unless told otherwise
+    let mode = args
+        .mode
+        .or(old_info.as_ref().map(|i| i.size_mode))
+        .unwrap_or_default();
+    let delta = diff_trees(&old, &new, mode);
+
+    println!("From: {}", describe_source(old_path, old_info.as_ref()));
+    println!("To:   {}\n", describe_source(new_path, new_info.as_ref()));
+    print_diff(&delta, args.top, args.depth, mode);
+    0
+}
+
+/// Print the delta tree, biggest growers first, skipping unchanged entries
+fn print_diff(root: &DeltaEntry, top: usize, depth: usize, mode: SizeMode) {
+    let mut out = io::stdout().lock();
+
+    let _ = writeln!(
+        out,
+        "{:>10}  {:>10}  {:>13}  {:<9}  PATH",
+        "OLD", "NEW", "DELTA", "CHANGE"
+    );
+    print_delta_line(&mut out, root, &root.path.display().to_string(), mode);
+    print_delta_children(&mut out, root, top, depth, mode, 1);
+
+    let _ = writeln!(
+        out,
+        "\n{} added, {} removed, {} grown, {} shrunk",
+        root.count(Change::Added),
+        root.count(Change::Removed),
+        root.count(Change::Grown),
+        root.count(Change::Shrunk)
+    );
+}
+
+fn print_delta_children(
+    out: &mut impl Write,
+    entry: &DeltaEntry,
+    top: usize,
+    max_depth: usize,
+    mode: SizeMode,
+    depth: usize,
+) {
+    if depth > max_depth {
+        return;
+    }
+
+    let indent = "  ".repeat(depth);
+    let changed = entry
+        .children
+        .iter()
+        .filter(|c| c.change != Change::Unchanged);
+    for child in changed.take(top) {
+        let suffix = if child.is_file { "" } else { "/" };
+        let label = format!("{}{}{}", indent, child.name.to_string_lossy(), suffix);
+        print_delta_line(out, child, &label, mode);
+        if !child.is_file {
+            print_delta_children(out, child, top, max_depth, mode, depth + 1);
+        }
+    }
+}
+
+fn print_delta_line(out: &mut impl Write, entry: &DeltaEntry, label: &str, mode: SizeMode) {
+    let _ = writeln!(
+        out,
+        "{:>10}  {:>10}  {:>13}  {:<9}  {}",
+        format_amount(entry.old_size, mode),
+        format_amount(entry.new_size, mode),
+        format_delta(entry.delta(), mode),
+        entry.change.label(),
+        label
+    );
+}
+
+fn run_owners(path: &Path, args: &Args) -> i32 {
+    let (info, tree) = match load_or_scan(path, &args.options) {
+        Ok(tree) => tree,
+        Err(e) => {
+            eprintln!("scorch: {}", e);
+            return 1;
+        }
+    };
+    let mode = args
+        .mode
+        .or(info.as_ref().map(|i| i.size_mode))
+        .unwrap_or_default();
+    let total = tree.get(tree.root()).size_in(mode);
+    let usage = owner_usage(&tree, tree.root(), mode);
+    let names = OwnerNames::load();
+
+    println!("Owners in {}\n", describe_source(path, info.as_ref()));
+    let mut out = io::stdout().lock();
+    let users = usage
+        .users
+        .iter()
+        .map(|&(uid, usage)| (names.user(uid), usage));
+    print_owners(&mut out, "USER", users, total, args.top, mode);
+    let _ = writeln!(out);
+    let groups = usage
+        .groups
+        .iter()
+        .map(|&(gid, usage)| (names.group(gid), usage));
+    print_owners(&mut out, "GROUP", groups, total, args.top, mode);
+    0
+}
+
+/// Print one owner table, largest first, folding everyone past `top` into one line
+fn print_owners(
+    out: &mut impl Write,
+    heading: &str,
+    owners: impl ExactSizeIterator<Item = (String, Usage)>,
+    total: u64,
+    top: usize,
+    mode: SizeMode,
+) {
+    let size_heading = match mode {
+        SizeMode::Inodes => "INODES",
+        SizeMode::Apparent | SizeMode::Allocated => "SIZE",
+    };
+    let _ = writeln!(
+        out,
+        "{:>10}  {:>6}  {:>8}  {:>8}  {}",
+        size_heading, "SHARE", "FILES", "FOLDERS", heading
+    );
+
+    let count = owners.len();
+    let mut rest = Usage::default();
+    for (i, (name, usage)) in owners.enumerate() {
+        if i < top {
+            print_owner_line(out, &name, &usage, total, mode);
+        } else {
+            rest.add(&usage);
+        }
+    }
+    if count > top {
+        let label = format!("... {} more", count - top);
+        print_owner_line(out, &label, &rest, total, mode);
+    }
+}
+
+fn print_owner_line(out: &mut impl Write, label: &str, usage: &Usage, total: u64, mode: SizeMode) {
+    let size = usage.size_in(mode);
+    let _ = writeln!(
+        out,
+        "{:>10}  {:>6}  {:>8}  {:>8}  {}",
+        format_amount(size, mode),
+        format_share(size, total),
+        format_count(usage.files),
+        format_count(usage.dirs),
+        label
+    );
+}
+
+fn run_exec_plan(file: &Path, args: &Args) -> i32 {
+    let plan = match plan::load(file) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("scorch: {}", e);
+            return 1;
+        }
+    };
+
+    println!(
+        "Plan made on {}, {} - {} entries, {}",
+        plan.hostname,
+        format_timestamp(plan.created),
+        plan.entries.len(),
+        format_size(plan.total_size())
+    );
+    let host = snapshot::hostname();
+    if plan.hostname != host {
+        println!("Warning: this is {}, not {}", host, plan.hostname);
+    }
+    println!();
+
+    let mode = if args.permanent {
+        DeleteMode::Permanent
+    } else {
+        DeleteMode::Trash
+    };
+    let mut done = 0;
+    let mut freed = 0;
+    for entry in &plan.entries {
+        let outcome = match check_plan_entry(entry) {
+            Err(reason) => Err(reason),
+            Ok(()) if args.dry_run => Ok("would be deleted"),
+            Ok(()) => match delete_entry(&entry.path, mode) {
+                DeleteResult::Trashed(_) => Ok("moved to the Trash"),
+                DeleteResult::Success => Ok("deleted"),
+                DeleteResult::Incomplete(failures) => Err(format!(
+                    "partly deleted, {} item(s) left behind: {}: {}",
+                    failures.len(),
+                    failures[0].path.display(),
+                    failures[0].message
+                )),
+                DeleteResult::ProtectedPath => Err("protected path".to_string()),
+                DeleteResult::NotFound => Err("no longer exists".to_string()),
+                DeleteResult::PermissionDenied(e) => Err(format!("permission denied: {}", e)),
+                DeleteResult::Error(e) => Err(e),
+            },
+        };
+
+        let (status, detail) = match outcome {
+            Ok(detail) => {
+                done += 1;
+                freed += entry.size;
+                ("OK", detail.to_string())
+            }
+            Err(reason) => ("SKIP", reason),
+        };
+        println!(
+            "{:<4}  {:>10}  {} - {}",
+            status,
+            format_size(entry.size),
+            entry.path.display(),
+            detail
+        );
+    }
+
+    println!(
+        "\n{} of {} entries {} - {} {}",
+        done,
+        plan.entries.len(),
+        if args.dry_run { "pass" } else { "burned" },
+        format_size(freed),
+        if args.dry_run {
+            "would be freed"
+        } else {
+            "freed"
+        }
+    );
+    if done == plan.entries.len() {
+        0
+    } else {
+        1
+    }
+}
+
+/// Check a plan entry is still safe to delete: not protected, and unchanged
+/// in type, size and modification time since the plan was made
+fn check_plan_entry(entry: &PlanEntry) -> Result<(), String> {
+    if let Some(rule) = policy::global().protecting_rule(&entry.path) {
+        return Err(format!("protected ({})", rule.source));
+    }
+    let measured = plan::measure(&entry.path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => "no longer exists".to_string(),
+        _ => format!("cannot check: {}", e),
+    })?;
+    let now = &measured.entry;
+    // Unreadable parts explain a mismatch, but needn't cause one
+    let unreadable = match measured.unreadable.len() {
+        0 => String::new(),
+        count => format!("; {} unreadable inside", count),
+    };
+
+    if now.is_dir != entry.is_dir {
+        return Err("changed type since the plan".to_string());
+    }
+    if now.size != entry.size {
+        return Err(format!(
+            "size changed since the plan ({} bytes, was {}{})",
+            now.size, entry.size, unreadable
+        ));
+    }
+    if now.mtime != entry.mtime {
+        return Err(format!(
+            "modified since the plan (at {}{})",
+            format_timestamp(now.mtime.max(0) as u64),
+            unreadable
+        ));
+    }
+    Ok(())
+}
+
+/// Print a size-sorted, indented table of the scanned tree
+fn print_report(tree: &ScanTree, top: usize, depth: usize, mode: SizeMode) {
+    let root = tree.get(tree.root());
+    let total = root.size_in(mode);
+    let mut out = io::stdout().lock();
+
+    let heading = match mode {
+        SizeMode::Inodes => "INODES",
+        SizeMode::Apparent | SizeMode::Allocated => "SIZE",
+    };
+    let _ = writeln!(out, "{:>10}  {:>6}  PATH", heading, "SHARE");
+    let _ = writeln!(
+        out,
+        "{:>10}  {:>6}  {}",
+        format_amount(total, mode),
+        format_share(total, total),
+        tree.root_path().display()
+    );
+    print_children(&mut out, tree, tree.root(), top, depth, mode, 1);
+    let _ = writeln!(
+        out,
+        "\n{} items scorched ({} files, {} folders) - {} apparent, {} on disk, {} inodes",
+        root.item_count(),
+        root.files,
+        root.dirs,
+        format_size(root.size),
+        format_size(root.disk_size),
+        root.inodes
+    );
+
+    let mounts = tree.skipped_mount_points();
+    if !mounts.is_empty() {
+        let _ = writeln!(out, "\nNot traversed (other filesystems):");
+        for path in mounts {
+            let _ = writeln!(out, "  {}", path.display());
+        }
+    }
+}
+
+fn print_children(
+    out: &mut impl Write,
+    tree: &ScanTree,
+    id: NodeId,
+    top: usize,
+    max_depth: usize,
+    mode: SizeMode,
+    depth: usize,
+) {
+    if depth > max_depth {
+        return;
+    }
+
+    let total = tree.get(tree.root()).size_in(mode);
+    let indent = "  ".repeat(depth);
+    let children = &tree.get(id).children;
+    for &child_id in children.iter().take(top) {
+        let child = tree.get(child_id);
+        let size = child.size_in(mode);
+        let suffix = if child.mount_point {
+            "/ [mount point, not traversed]".to_string()
+        } else if child.is_hard_linked() {
+            format!(" [{} links]", child.nlink)
+        } else if child.is_file {
+            String::new()
+        } else {
+            "/".to_string()
+        };
+        let _ = writeln!(
+            out,
+            "{:>10}  {:>6}  {}{}{}",
+            format_amount(size, mode),
+            format_share(size, total),
+            indent,
+            tree.name(child_id),
+            suffix
+        );
+        if !child.is_file {
+            print_children(out, tree, child_id, top, max_depth, mode, depth + 1);
+        }
+    }
+
+    // Summarise whatever didn't make the cut
+    let rest = &children[children.len().min(top)..];
+    if !rest.is_empty() {
+        let size: u64 = rest.iter().map(|&c| tree.get(c).size_in(mode)).sum();
+        let _ = writeln!(
+            out,
+            "{:>10}  {:>6}  {}... {} more",
+            format_amount(size, mode),
+            format_share(size, total),
+            indent,
+            rest.len()
+        );
+    }
+}
+
+/// An amount for a table column; inode counts go without a unit to keep it narrow
+fn format_amount(amount: u64, mode: SizeMode) -> String {
+    match mode {
+        SizeMode::Inodes => format_count(amount),
+        SizeMode::Apparent | SizeMode::Allocated => format_size(amount),
+    }
+}
+
+fn format_share(size: u64, total: u64) -> String {
+    if total == 0 {
+        return "-".to_string();
+    }
+    format!("{:.1}%", size as f64 * 100.0 / total as f64)
+}
@@ -0,0 +1,267 @@
+use crate::model::SizeMode;
+use crate::tree::{NodeId, ScanTree};
+use std::cmp::Reverse;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// How an entry changed between two scans
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Grown,
+    Shrunk,
+    Unchanged,
+}
+
+impl Change {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Grown => "grown",
+            Change::Shrunk => "shrunk",
+            Change::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// One node of the tree describing what changed between two scans
+#[derive(Debug, Clone)]
+pub struct DeltaEntry {
+    pub path: PathBuf,
+    /// File name exactly as on disk; the root goes by its full path if it has none
+    pub name: OsString,
+    pub is_file: bool,
+    pub old_size: u64,
+    pub new_size: u64,
+    pub change: Change,
+    /// Children, biggest growth first
+    pub children: Vec<DeltaEntry>,
+}
+
+impl DeltaEntry {
+    /// Signed size change in bytes
+    pub fn delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+
+    /// Find the entry for `path` by walking down from this node
+    pub fn find(&self, path: &Path) -> Option<&DeltaEntry> {
+        let rest = path.strip_prefix(&self.path).ok()?;
+        let mut current = self;
+        for component in rest.components() {
+            let name = component.as_os_str();
+            current = current.children.iter().find(|c| c.name == name)?;
+        }
+        Some(current)
+    }
+
+    /// Count the entries anywhere in this subtree with the given change
+    pub fn count(&self, change: Change) -> usize {
+        let own = usize::from(self.change == change);
+        own + self.children.iter().map(|c| c.count(change)).sum::<usize>()
+    }
+}
+
+/// Compare two scans of the same tree, matching entries by path
+///
+/// Paths are taken relative to each root, so a snapshot of `/data` can be
+/// compared with a later scan of `/data` or a copy mounted elsewhere.
+pub fn diff_trees(old: &ScanTree, new: &ScanTree, mode: SizeMode) -> DeltaEntry {
+    let trees = Trees { old, new, mode };
+    trees.diff_entry(
+        Some(old.root()),
+        Some(new.root()),
+        new.root_path().to_path_buf(),
+    )
+}
+
+/// Both sides of a comparison
+struct Trees<'a> {
+    old: &'a ScanTree,
+    new: &'a ScanTree,
+    mode: SizeMode,
+}
+
+impl Trees<'_> {
+    fn diff_entry(&self, old: Option<NodeId>, new: Option<NodeId>, path: PathBuf) -> DeltaEntry {
+        let (either, either_id) = new
+            .map(|id| (self.new, id))
+            .or(old.map(|id| (self.old, id)))
+            .expect("diff_entry needs at least one side");
+        let old_node = old.map(|id| self.old.get(id));
+        let new_node = new.map(|id| self.new.get(id));
+        let old_size = old_node.map_or(0, |e| e.size_in(self.mode));
+        let new_size = new_node.map_or(0, |e| e.size_in(self.mode));
+
+        let change = match (old, new) {
+            (None, _) => Change::Added,
+            (_, None) => Change::Removed,
+            _ if new_size > old_size => Change::Grown,
+            _ if new_size < old_size => Change::Shrunk,
+            _ => Change::Unchanged,
+        };
+
+        // Pair up children by name; anything unmatched was added or removed
+        let mut children = Vec::new();
+        if let Some(new_node) = new_node {
+            for &child in &new_node.children {
+                let name = self.new.file_name(child);
+                let old_child = old.and_then(|old| self.old.child(old, name));
+                children.push(self.diff_entry(old_child, Some(child), path.join(name)));
+            }
+        }
+        if let Some(old_node) = old_node {
+            for &child in &old_node.children {
+                let name = self.old.file_name(child);
+                if new.and_then(|new| self.new.child(new, name)).is_none() {
+                    children.push(self.diff_entry(Some(child), None, path.join(name)));
+                }
+            }
+        }
+        children.sort_by_key(|c| Reverse(c.delta()));
+
+        DeltaEntry {
+            name: path.file_name().unwrap_or(path.as_os_str()).to_os_string(),
+            path,
+            is_file: either.get(either_id).is_file,
+            old_size,
+            new_size,
+            change,
+            children,
+        }
+    }
+}
+
+/// Format a signed change measured by `mode`, e.g. "+1.20 GB" or "-512 B"
+pub fn format_delta(delta: i64, mode: SizeMode) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{}{}", sign, mode.format(delta.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::DirEntry;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    /// A tree rooted at `root` holding `dirs`, then files of the given sizes
+    fn tree(root: &str, dirs: &[&str], files: &[(&str, u64)]) -> ScanTree {
+        let root = Path::new(root);
+        let mut tree = ScanTree::from_entry(DirEntry::new_dir(root.to_path_buf()));
+        for dir in dirs {
+            tree.insert(DirEntry::new_dir(root.join(dir)), SizeMode::Apparent);
+        }
+        for &(path, size) in files {
+            let file = DirEntry::new_file(root.join(path), size, size);
+            tree.insert(file, SizeMode::Apparent);
+        }
+        tree
+    }
+
+    fn changes(entry: &DeltaEntry) -> Vec<(String, Change, i64)> {
+        entry
+            .children
+            .iter()
+            .map(|c| (c.name.to_string_lossy().into_owned(), c.change, c.delta()))
+            .collect()
+    }
+
+    #[test]
+    fn classifies_what_changed_biggest_growth_first() {
+        let old = tree(
+            "/r",
+            &[],
+            &[
+                ("grown.txt", 100),
+                ("shrunk.txt", 50),
+                ("same.txt", 10),
+                ("removed.txt", 70),
+            ],
+        );
+        let new = tree(
+            "/r",
+            &[],
+            &[
+                ("grown.txt", 300),
+                ("shrunk.txt", 20),
+                ("same.txt", 10),
+                ("added.txt", 40),
+            ],
+        );
+
+        let delta = diff_trees(&old, &new, SizeMode::Apparent);
+        assert_eq!(delta.change, Change::Grown);
+        assert_eq!((delta.old_size, delta.new_size), (230, 370));
+        assert_eq!(
+            changes(&delta),
+            [
+                ("grown.txt".to_string(), Change::Grown, 200),
+                ("added.txt".to_string(), Change::Added, 40),
+                ("same.txt".to_string(), Change::Unchanged, 0),
+                ("shrunk.txt".to_string(), Change::Shrunk, -30),
+                ("removed.txt".to_string(), Change::Removed, -70),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_entries_by_path_below_each_root() {
+        let old = tree(
+            "/mnt/old",
+            &["src", "src/gone"],
+            &[("src/a.rs", 10), ("src/gone/b.rs", 5)],
+        );
+        let new = tree(
+            "/data",
+            &["src", "src/new"],
+            &[("src/a.rs", 10), ("src/new/c.rs", 8)],
+        );
+
+        let delta = diff_trees(&old, &new, SizeMode::Apparent);
+        let src = delta.find(Path::new("/data/src")).unwrap();
+        assert_eq!(src.change, Change::Grown);
+        assert_eq!(
+            changes(src),
+            [
+                ("new".to_string(), Change::Added, 8),
+                ("a.rs".to_string(), Change::Unchanged, 0),
+                ("gone".to_string(), Change::Removed, -5),
+            ]
+        );
+        let gone = delta.find(Path::new("/data/src/gone/b.rs")).unwrap();
+        assert_eq!((gone.old_size, gone.new_size), (5, 0));
+        assert_eq!(delta.count(Change::Added), 2);
+        assert_eq!(delta.count(Change::Removed), 2);
+        assert!(delta.find(Path::new("/mnt/old/src")).is_none());
+    }
+
+    #[test]
+    fn finds_names_that_are_not_utf8() {
+        // Both names read as U+FFFD once made lossy
+        let first = OsStr::from_bytes(b"\xff");
+        let second = OsStr::from_bytes(b"\xfe");
+        let file = |name, size| DirEntry::new_file(Path::new("/r").join(name), size, size);
+        let mut old = tree("/r", &[], &[]);
+        old.insert(file(first, 1), SizeMode::Apparent);
+        let mut new = old.clone();
+        new.insert(file(second, 2), SizeMode::Apparent);
+
+        let delta = diff_trees(&old, &new, SizeMode::Apparent);
+        let found = delta.find(&Path::new("/r").join(second)).unwrap();
+        assert_eq!(found.name, second);
+        assert_eq!(found.change, Change::Added);
+        let found = delta.find(&Path::new("/r").join(first)).unwrap();
+        assert_eq!(found.change, Change::Unchanged);
+    }
+
+    #[test]
+    fn formats_signed_deltas() {
+        assert_eq!(format_delta(1536, SizeMode::Apparent), "+1.50 KB");
+        assert_eq!(format_delta(-512, SizeMode::Allocated), "-512 B");
+        assert_eq!(format_delta(0, SizeMode::Apparent), "+0 B");
+        assert_eq!(format_delta(-3, SizeMode::Inodes), "-3 inodes");
+    }
+}
@@ -1,14 +1,30 @@
 mod actions;
 mod app;
+mod cli;
+mod diff;
 mod model;
+mod owners;
+mod plan;
+mod policy;
+mod remove;
 mod scanner;
+mod snapshot;
 mod sunburst;
+mod trash;
+mod tree;
 mod ui;
+mod watch;
 
 use gtk4::prelude::*;
 use gtk4::Application;
 
 fn main() {
+    // Any arguments select the headless CLI, which never touches GTK
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     let app = Application::builder()
         .application_id("com.scorch.app")
         .build();
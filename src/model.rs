@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 
 /// File type categories for color coding
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -56,92 +58,169 @@ impl FileType {
     }
 }
 
+/// Which size figure drives the visualization and reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeMode {
+    /// Logical file length, as reported by `ls -l`
+    #[default]
+    Apparent,
+    /// Blocks actually allocated on disk, as reported by `du`
+    Allocated,
+    /// Inodes used, as reported by `du --inodes`
+    Inodes,
+}
+
+impl SizeMode {
+    /// Format an amount measured this way, e.g. "1.20 GB" or "12.3k inodes"
+    pub fn format(&self, amount: u64) -> String {
+        match self {
+            SizeMode::Apparent | SizeMode::Allocated => format_size(amount),
+            SizeMode::Inodes => format!("{} inodes", format_count(amount)),
+        }
+    }
+}
+
+/// Timestamps and ownership of one file or directory, as `stat` reports them
+///
+/// Times are seconds since the Unix epoch; a directory's are its own, not
+/// those of anything inside it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stat {
+    /// Last modification of the contents
+    pub mtime: i64,
+    /// Last access
+    pub atime: i64,
+    /// Last change to the contents or the inode (permissions, owner, links)
+    pub ctime: i64,
+    pub uid: u32,
+    pub gid: u32,
+    /// File type and permission bits (`st_mode`)
+    pub mode: u32,
+}
+
+impl Stat {
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            mtime: metadata.mtime(),
+            atime: metadata.atime(),
+            ctime: metadata.ctime(),
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mode: metadata.mode(),
+        }
+    }
+
+    /// Whether this came from the filesystem; snapshots from before it was
+    /// recorded leave it zeroed
+    pub fn is_recorded(&self) -> bool {
+        self.mode != 0
+    }
+}
+
 /// A directory or file entry with size information
 #[derive(Debug, Clone)]
 pub struct DirEntry {
     pub path: PathBuf,
-    pub name: String,
+    /// Apparent size in bytes, including everything beneath a directory
     pub size: u64,
+    /// Allocated size in bytes (`st_blocks * 512`), including everything beneath a directory
+    pub disk_size: u64,
+    /// Files in this subtree (1 for a file)
+    pub files: u64,
+    /// Directories in this subtree, itself included
+    pub dirs: u64,
+    /// Inodes used by this subtree; a hard link counted elsewhere adds none
+    pub inodes: u64,
     pub file_type: FileType,
     pub children: Vec<DirEntry>,
     pub is_file: bool,
+    /// Number of hard links to this file's inode
+    pub nlink: u64,
+    /// Mount point of another filesystem that was not traversed
+    pub mount_point: bool,
+    pub stat: Stat,
 }
 
 impl DirEntry {
     /// Create a new directory entry
     pub fn new_dir(path: PathBuf) -> Self {
-        let name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| path.to_string_lossy().to_string());
         Self {
             path,
-            name,
             size: 0,
+            disk_size: 0,
+            files: 0,
+            dirs: 1,
+            inodes: 1,
             file_type: FileType::Directory,
             children: Vec::new(),
             is_file: false,
+            nlink: 1,
+            mount_point: false,
+            stat: Stat::default(),
+        }
+    }
+
+    /// Create a marker for a mount point the scan stopped at
+    pub fn new_mount_point(path: PathBuf) -> Self {
+        Self {
+            mount_point: true,
+            ..Self::new_dir(path)
         }
     }
 
     /// Create a new file entry
-    pub fn new_file(path: PathBuf, size: u64) -> Self {
-        let name = path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_default();
+    pub fn new_file(path: PathBuf, size: u64, disk_size: u64) -> Self {
         let file_type = path
             .extension()
             .map(|ext| FileType::from_extension(&ext.to_string_lossy()))
             .unwrap_or(FileType::Other);
         Self {
             path,
-            name,
             size,
+            disk_size,
+            files: 1,
+            dirs: 0,
+            inodes: 1,
             file_type,
             children: Vec::new(),
             is_file: true,
+            nlink: 1,
+            mount_point: false,
+            stat: Stat::default(),
         }
     }
 
-    /// Calculate total size including all children
-    pub fn total_size(&self) -> u64 {
-        if self.is_file {
-            self.size
-        } else {
-            self.children.iter().map(|c| c.total_size()).sum()
+    /// Total size measured the given way
+    pub fn size_in(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.size,
+            SizeMode::Allocated => self.disk_size,
+            SizeMode::Inodes => self.inodes,
         }
     }
 
-    /// Get the number of items (files + directories) including self
-    pub fn item_count(&self) -> usize {
-        1 + self.children.iter().map(|c| c.item_count()).sum::<usize>()
-    }
-
-    /// Sort children by size (largest first)
-    pub fn sort_by_size(&mut self) {
-        self.children.sort_by(|a, b| b.total_size().cmp(&a.total_size()));
-        for child in &mut self.children {
-            child.sort_by_size();
+    /// Recompute a directory's totals from those of its children
+    pub fn update_totals(&mut self) {
+        if self.is_file {
+            return;
         }
+        self.size = self.children.iter().map(|c| c.size).sum();
+        self.disk_size = self.children.iter().map(|c| c.disk_size).sum();
+        self.files = self.children.iter().map(|c| c.files).sum();
+        self.dirs = 1 + self.children.iter().map(|c| c.dirs).sum::<u64>();
+        self.inodes = 1 + self.children.iter().map(|c| c.inodes).sum::<u64>();
     }
 
-    /// Find entry by path
-    pub fn find_by_path(&self, target: &PathBuf) -> Option<&DirEntry> {
-        if &self.path == target {
-            return Some(self);
+    /// Drop everything in this subtree that no longer exists on disk
+    ///
+    /// Returns false if this entry itself is gone.
+    pub fn prune_missing(&mut self) -> bool {
+        if std::fs::symlink_metadata(&self.path).is_err() {
+            return false;
         }
-        for child in &self.children {
-            if let Some(found) = child.find_by_path(target) {
-                return Some(found);
-            }
-        }
-        None
-    }
-
-    /// Get parent path
-    pub fn parent_path(&self) -> Option<PathBuf> {
-        self.path.parent().map(|p| p.to_path_buf())
+        self.children.retain_mut(|child| child.prune_missing());
+        self.update_totals();
+        true
     }
 }
 
@@ -165,26 +244,86 @@ pub fn format_size(bytes: u64) -> String {
     }
 }
 
-/// Protected system paths that cannot be deleted
-pub const PROTECTED_PATHS: &[&str] = &[
-    "/",
-    "/usr",
-    "/etc",
-    "/bin",
-    "/sbin",
-    "/lib",
-    "/lib64",
-    "/boot",
-    "/dev",
-    "/proc",
-    "/sys",
-    "/run",
-    "/var",
-    "/root",
-];
-
-/// Check if a path is protected from deletion
-pub fn is_protected_path(path: &PathBuf) -> bool {
-    let path_str = path.to_string_lossy();
-    PROTECTED_PATHS.iter().any(|p| path_str == *p)
+/// Format a count compactly, e.g. "982", "12.3k" or "1.25M"
+pub fn format_count(count: u64) -> String {
+    const K: u64 = 1_000;
+    const M: u64 = K * 1_000;
+    const G: u64 = M * 1_000;
+
+    if count >= G {
+        format!("{:.2}G", count as f64 / G as f64)
+    } else if count >= M {
+        format!("{:.2}M", count as f64 / M as f64)
+    } else if count >= 10 * K {
+        format!("{:.1}k", count as f64 / K as f64)
+    } else {
+        count.to_string()
+    }
+}
+
+/// Format `st_mode` the way `ls -l` does, e.g. "drwxr-xr-x"
+pub fn format_mode(mode: u32) -> String {
+    let kind = match mode & libc::S_IFMT {
+        libc::S_IFDIR => 'd',
+        libc::S_IFLNK => 'l',
+        libc::S_IFCHR => 'c',
+        libc::S_IFBLK => 'b',
+        libc::S_IFIFO => 'p',
+        libc::S_IFSOCK => 's',
+        _ => '-',
+    };
+    let mut text = String::with_capacity(10);
+    text.push(kind);
+    // Owner, group, other; setuid, setgid and sticky replace the matching x
+    let classes = [
+        (6, libc::S_ISUID, 's'),
+        (3, libc::S_ISGID, 's'),
+        (0, libc::S_ISVTX, 't'),
+    ];
+    for (shift, special, set_char) in classes {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 0o1 != 0, mode & special != 0) {
+            (true, true) => set_char,
+            (false, true) => set_char.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
+/// Check if a path is protected from deletion by the protected-path policy
+pub fn is_protected_path(path: &Path) -> bool {
+    crate::policy::global().is_protected(path)
+}
+
+/// Format seconds since the Unix epoch as a UTC date and time
+pub fn format_timestamp(secs: u64) -> String {
+    let rem = secs % 86_400;
+    format!(
+        "{} {:02}:{:02} UTC",
+        format_date(secs),
+        rem / 3_600,
+        rem % 3_600 / 60
+    )
+}
+
+/// Format seconds since the Unix epoch as a UTC date, e.g. "2024-05-01"
+pub fn format_date(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+
+    // Civil-from-days (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
 }
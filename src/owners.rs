@@ -0,0 +1,274 @@
+//! Who owns the bytes: usage totalled by user and by group
+//!
+//! Owners are shown by name where `/etc/passwd` and `/etc/group` know them.
+//! Those files are read directly, so users that only exist in LDAP or another
+//! NSS source show up as their numeric ids.
+
+use crate::model::SizeMode;
+use crate::tree::{NodeId, ScanTree};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+
+/// What one owner's entries add up to
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub size: u64,
+    pub disk_size: u64,
+    pub files: u64,
+    pub dirs: u64,
+    pub inodes: u64,
+}
+
+impl Usage {
+    /// Size measured the given way
+    pub fn size_in(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.size,
+            SizeMode::Allocated => self.disk_size,
+            SizeMode::Inodes => self.inodes,
+        }
+    }
+
+    /// Fold in another owner's usage
+    pub fn add(&mut self, other: &Usage) {
+        self.size += other.size;
+        self.disk_size += other.disk_size;
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.inodes += other.inodes;
+    }
+}
+
+/// Usage by user and by group, largest first
+///
+/// An owner of `None` stands for entries whose owner wasn't recorded, such as
+/// those read from an old snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct OwnerUsage {
+    pub users: Vec<(Option<u32>, Usage)>,
+    pub groups: Vec<(Option<u32>, Usage)>,
+}
+
+/// Total the subtree at `id` by owner, ranking owners by `mode`
+///
+/// Each entry counts towards its own owner only: a directory owned by root
+/// full of alice's files puts the bytes under alice.
+pub fn owner_usage(tree: &ScanTree, id: NodeId, mode: SizeMode) -> OwnerUsage {
+    let mut users: HashMap<Option<u32>, Usage> = HashMap::new();
+    let mut groups: HashMap<Option<u32>, Usage> = HashMap::new();
+
+    let mut stack = vec![id];
+    while let Some(id) = stack.pop() {
+        let node = tree.get(id);
+        let recorded = node.stat.is_recorded();
+        let user = users.entry(recorded.then_some(node.stat.uid)).or_default();
+        let group = groups.entry(recorded.then_some(node.stat.gid)).or_default();
+        for usage in [user, group] {
+            if node.is_file {
+                usage.size += node.size;
+                usage.disk_size += node.disk_size;
+                usage.files += 1;
+                usage.inodes += node.inodes;
+            } else {
+                usage.dirs += 1;
+                usage.inodes += 1;
+            }
+        }
+        stack.extend(&node.children);
+    }
+
+    OwnerUsage {
+        users: ranked(users, mode),
+        groups: ranked(groups, mode),
+    }
+}
+
+fn ranked(usage: HashMap<Option<u32>, Usage>, mode: SizeMode) -> Vec<(Option<u32>, Usage)> {
+    let mut usage: Vec<_> = usage.into_iter().collect();
+    // Ties broken by id so the order (and owner colours) don't flicker
+    usage.sort_by_key(|&(owner, usage)| (Reverse(usage.size_in(mode)), owner));
+    usage
+}
+
+/// User and group names by id
+#[derive(Debug, Clone, Default)]
+pub struct OwnerNames {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl OwnerNames {
+    /// Read this machine's users and groups; missing files leave them unnamed
+    pub fn load() -> Self {
+        Self {
+            users: read_ids("/etc/passwd"),
+            groups: read_ids("/etc/group"),
+        }
+    }
+
+    /// Name of a user, or its uid if it has none
+    pub fn user(&self, uid: Option<u32>) -> String {
+        Self::name(&self.users, uid)
+    }
+
+    /// Name of a group, or its gid if it has none
+    pub fn group(&self, gid: Option<u32>) -> String {
+        Self::name(&self.groups, gid)
+    }
+
+    fn name(names: &HashMap<u32, String>, id: Option<u32>) -> String {
+        match id {
+            Some(id) => names.get(&id).cloned().unwrap_or_else(|| id.to_string()),
+            None => "unknown".to_string(),
+        }
+    }
+}
+
+/// Map ids to names from a file laid out like `/etc/passwd`: `name:password:id:...`
+fn read_ids(path: &str) -> HashMap<u32, String> {
+    fs::read_to_string(path)
+        .map(|text| parse_ids(&text))
+        .unwrap_or_default()
+}
+
+fn parse_ids(text: &str) -> HashMap<u32, String> {
+    let mut names = HashMap::new();
+    for line in text.lines() {
+        let mut fields = line.split(':');
+        let (Some(name), Some(_), Some(id)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if name.starts_with('#') {
+            continue;
+        }
+        if let Ok(id) = id.parse() {
+            // The first entry for an id is the one `ls -l` shows
+            names.entry(id).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{DirEntry, Stat};
+    use std::path::{Path, PathBuf};
+
+    fn owned(mut entry: DirEntry, uid: u32, gid: u32) -> DirEntry {
+        entry.stat = Stat {
+            uid,
+            gid,
+            mode: if entry.is_file { 0o100644 } else { 0o40755 },
+            ..Default::default()
+        };
+        entry
+    }
+
+    fn file(path: &str, size: u64, uid: u32, gid: u32) -> DirEntry {
+        let entry = DirEntry::new_file(PathBuf::from(path), size, size);
+        owned(entry, uid, gid)
+    }
+
+    /// /r owned by root, holding files and a directory of two users, a hard
+    /// link counted elsewhere and a file from an old snapshot with no owner
+    fn sample() -> ScanTree {
+        let mut tree = ScanTree::from_entry(owned(DirEntry::new_dir("/r".into()), 0, 0));
+        let root = tree.root();
+        tree.add_child(root, file("/r/a.txt", 100, 1000, 100));
+        tree.add_child(root, file("/r/b.txt", 50, 1001, 100));
+        let link = tree.add_child(root, file("/r/link.txt", 30, 1001, 100));
+        tree.add_child(root, DirEntry::new_file("/r/old.txt".into(), 7, 7));
+        let sub = tree.add_child(root, owned(DirEntry::new_dir("/r/sub".into()), 1000, 1000));
+        tree.add_child(sub, file("/r/sub/c.txt", 30, 1000, 1000));
+        tree.finish_dir(sub, SizeMode::Apparent);
+        tree.finish_dir(root, SizeMode::Apparent);
+        tree.count_elsewhere(link, SizeMode::Apparent);
+        tree
+    }
+
+    /// (owner, size, files, dirs, inodes) of each owner, in ranked order
+    fn summary(usage: &[(Option<u32>, Usage)]) -> Vec<(Option<u32>, u64, u64, u64, u64)> {
+        usage
+            .iter()
+            .map(|(owner, u)| (*owner, u.size, u.files, u.dirs, u.inodes))
+            .collect()
+    }
+
+    #[test]
+    fn totals_each_entry_under_its_own_user_and_group() {
+        let tree = sample();
+        let usage = owner_usage(&tree, tree.root(), SizeMode::Apparent);
+        assert_eq!(
+            summary(&usage.users),
+            [
+                (Some(1000), 130, 2, 1, 3),
+                (Some(1001), 50, 2, 0, 1),
+                (None, 7, 1, 0, 1),
+                (Some(0), 0, 0, 1, 1),
+            ]
+        );
+        assert_eq!(
+            summary(&usage.groups),
+            [
+                (Some(100), 150, 3, 0, 2),
+                (Some(1000), 30, 1, 1, 2),
+                (None, 7, 1, 0, 1),
+                (Some(0), 0, 0, 1, 1),
+            ]
+        );
+
+        let sub = tree.find(Path::new("/r/sub")).unwrap();
+        let usage = owner_usage(&tree, sub, SizeMode::Apparent);
+        assert_eq!(summary(&usage.users), [(Some(1000), 30, 1, 1, 2)]);
+    }
+
+    #[test]
+    fn ranks_by_the_chosen_measure_then_by_id() {
+        let tree = sample();
+        let usage = owner_usage(&tree, tree.root(), SizeMode::Inodes);
+        let owners: Vec<_> = usage.users.iter().map(|(owner, _)| *owner).collect();
+        assert_eq!(owners, [Some(1000), None, Some(0), Some(1001)]);
+    }
+
+    #[test]
+    fn parses_passwd_and_group_lines() {
+        let text = "\
+root:x:0:0:root:/root:/bin/bash
+# alice:x:1000:1000::/home/alice:/bin/sh
+#commented:x:5:5::/:/bin/false
+bob:x:1001:1001::/home/bob:/bin/sh
+broken-line
+short:x
+bad-id:x:abc:1::/:/bin/false
+toor:x:0:0::/root:/bin/sh
+wheel:x:10:alice,bob
+";
+        let mut ids: Vec<_> = parse_ids(text).into_iter().collect();
+        ids.sort();
+        let expected = [(0, "root"), (10, "wheel"), (1001, "bob")];
+        assert_eq!(ids, expected.map(|(id, name)| (id, name.to_string())));
+    }
+
+    #[test]
+    fn names_owners_or_falls_back_to_ids() {
+        let names = OwnerNames {
+            users: parse_ids("alice:x:1000:1000::/home/alice:/bin/sh"),
+            groups: parse_ids("staff:x:50:"),
+        };
+        assert_eq!(names.user(Some(1000)), "alice");
+        assert_eq!(names.user(Some(1001)), "1001");
+        assert_eq!(names.group(Some(50)), "staff");
+        assert_eq!(names.group(Some(1000)), "1000");
+        assert_eq!(names.user(None), "unknown");
+    }
+
+    #[test]
+    fn loads_this_machines_users() {
+        if !Path::new("/etc/passwd").exists() {
+            return;
+        }
+        assert_eq!(OwnerNames::load().user(Some(0)), "root");
+    }
+}
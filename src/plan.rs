@@ -0,0 +1,322 @@
+use crate::model::{format_size, format_timestamp};
+use crate::snapshot;
+use crate::tree::{NodeId, ScanTree};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current plan format version
+const VERSION: u32 = 1;
+
+/// Default file extension for plans
+pub const EXTENSION: &str = "json";
+
+/// A reviewable list of entries to delete, executed later by `scorch exec-plan`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    pub version: u32,
+    /// Host the plan was made on
+    pub hostname: String,
+    /// Seconds since the Unix epoch
+    pub created: u64,
+    pub entries: Vec<PlanEntry>,
+}
+
+/// One entry of a plan, with what it looked like when the plan was made
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Apparent size in bytes, including everything inside a directory
+    pub size: u64,
+    /// Newest modification time anywhere in the entry, in seconds since the epoch
+    pub mtime: i64,
+}
+
+impl PlanEntry {
+    /// Describe a scanned entry as the scan saw it
+    ///
+    /// This is what `measure` finds on disk later if nothing has changed,
+    /// except where a hard link inside the entry was counted under a path
+    /// outside it; such entries are skipped at execution time.
+    pub fn from_scan(tree: &ScanTree, id: NodeId) -> Self {
+        let node = tree.get(id);
+        let mut mtime = node.stat.mtime;
+        let mut stack = node.children.clone();
+        while let Some(child) = stack.pop() {
+            let child = tree.get(child);
+            mtime = mtime.max(child.stat.mtime);
+            stack.extend(&child.children);
+        }
+        Self {
+            path: tree.path(id),
+            is_dir: !node.is_file,
+            size: node.size,
+            mtime,
+        }
+    }
+}
+
+impl Plan {
+    /// Build a plan for entries described by `PlanEntry::from_scan`
+    pub fn new(entries: Vec<PlanEntry>) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            version: VERSION,
+            hostname: snapshot::hostname(),
+            created,
+            entries,
+        }
+    }
+
+    /// Total apparent size of every entry
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+}
+
+/// An entry as `measure` found it on disk
+#[derive(Debug)]
+pub struct Measured {
+    pub entry: PlanEntry,
+    /// Paths inside the entry that couldn't be read, and why
+    pub unreadable: Vec<(PathBuf, io::Error)>,
+}
+
+/// Measure an entry on disk the way a scan sees it
+///
+/// Directories are walked without following symlinks, which are skipped
+/// along with special files, and each inode counts once. Anything inside
+/// that can't be read is noted and left out, as a scan leaves it out.
+pub fn measure(path: &Path) -> io::Result<Measured> {
+    let metadata = fs::symlink_metadata(path)?;
+    let mut walk = Walk::default();
+    walk.visit(path, &metadata);
+    Ok(Measured {
+        entry: PlanEntry {
+            path: path.to_path_buf(),
+            is_dir: metadata.is_dir(),
+            size: walk.size,
+            mtime: walk.mtime,
+        },
+        unreadable: walk.unreadable,
+    })
+}
+
+#[derive(Default)]
+struct Walk {
+    size: u64,
+    mtime: i64,
+    seen_inodes: HashSet<(u64, u64)>,
+    unreadable: Vec<(PathBuf, io::Error)>,
+}
+
+impl Walk {
+    fn visit(&mut self, path: &Path, metadata: &fs::Metadata) {
+        self.mtime = self.mtime.max(metadata.mtime());
+        if metadata.is_dir() {
+            let items = match fs::read_dir(path) {
+                Ok(items) => items,
+                Err(e) => return self.unreadable.push((path.to_path_buf(), e)),
+            };
+            for item in items {
+                let visited = item.and_then(|item| {
+                    let item_metadata = item.metadata()?;
+                    // A scan leaves out symlinks and special files
+                    if item_metadata.is_dir() || item_metadata.is_file() {
+                        self.visit(&item.path(), &item_metadata);
+                    }
+                    Ok(())
+                });
+                if let Err(e) = visited {
+                    self.unreadable.push((path.to_path_buf(), e));
+                }
+            }
+        } else if metadata.is_file()
+            && (metadata.nlink() == 1 || self.seen_inodes.insert((metadata.dev(), metadata.ino())))
+        {
+            self.size += metadata.len();
+        }
+    }
+}
+
+/// Whether `path` can go in a plan, which holds paths as UTF-8 text
+///
+/// Anything else would be mangled into a different path on the way.
+pub fn is_plannable(path: &Path) -> bool {
+    path.to_str().is_some()
+}
+
+/// Where the shell script for the plan at `json_path` goes
+pub fn script_path(json_path: &Path) -> PathBuf {
+    json_path.with_extension("sh")
+}
+
+/// Write the plan as JSON to `path`, with a shell script version next to it
+///
+/// An existing script is never replaced, since someone may have reviewed or
+/// edited it.
+pub fn save(path: &Path, plan: &Plan) -> Result<PathBuf, String> {
+    if let Some(entry) = plan.entries.iter().find(|e| !is_plannable(&e.path)) {
+        return Err(format!(
+            "{} is not valid UTF-8, so it can't go in a plan",
+            entry.path.display()
+        ));
+    }
+    let script = script_path(path);
+    if fs::symlink_metadata(&script).is_ok() {
+        return Err(format!(
+            "{} already exists; move it away or choose another name",
+            script.display()
+        ));
+    }
+
+    let write_json = || -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, plan)?;
+        writeln!(out)?;
+        out.flush()
+    };
+    write_json().map_err(|e| format!("Cannot write plan {}: {}", path.display(), e))?;
+
+    let write_script = || -> io::Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&script)?;
+        let mut out = BufWriter::new(file);
+        write_script(&mut out, plan, path)?;
+        out.flush()?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+    };
+    write_script().map_err(|e| format!("Cannot write plan {}: {}", script.display(), e))?;
+    Ok(script)
+}
+
+/// Read a plan written by `save`
+pub fn load(path: &Path) -> Result<Plan, String> {
+    let read = || -> io::Result<Plan> {
+        let plan: Plan = serde_json::from_reader(io::BufReader::new(File::open(path)?))?;
+        if plan.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported plan version {}", plan.version),
+            ));
+        }
+        Ok(plan)
+    };
+    read().map_err(|e| format!("Cannot read plan {}: {}", path.display(), e))
+}
+
+fn write_script(out: &mut impl Write, plan: &Plan, json_path: &Path) -> io::Result<()> {
+    writeln!(out, "#!/bin/sh")?;
+    writeln!(
+        out,
+        "# Deletion plan made by scorch on {}, {}",
+        plan.hostname,
+        format_timestamp(plan.created)
+    )?;
+    writeln!(
+        out,
+        "# {} entries, {} in total",
+        plan.entries.len(),
+        format_size(plan.total_size())
+    )?;
+    writeln!(out, "#")?;
+    writeln!(
+        out,
+        "# This script deletes blindly. To re-check every entry first, run:"
+    )?;
+    writeln!(
+        out,
+        "#   scorch exec-plan {}",
+        shell_quote(&json_path.to_string_lossy())
+    )?;
+    writeln!(out)?;
+    writeln!(out, "set -eu")?;
+    writeln!(out)?;
+    for entry in &plan.entries {
+        writeln!(
+            out,
+            "rm -rf -- {}  # {}, modified {}",
+            shell_quote(&entry.path.to_string_lossy()),
+            format_size(entry.size),
+            format_timestamp(entry.mtime.max(0) as u64)
+        )?;
+    }
+    Ok(())
+}
+
+/// Single-quote a string for the shell
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::{scan_path, ScanOptions};
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn scanned_entries_match_what_is_on_disk() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("build");
+        fs::create_dir_all(root.join("out/deep")).unwrap();
+        fs::write(root.join("out/a.o"), vec![0u8; 3000]).unwrap();
+        fs::write(root.join("out/deep/b.o"), vec![0u8; 700]).unwrap();
+        fs::hard_link(root.join("out/a.o"), root.join("out/a-link.o")).unwrap();
+        symlink("/etc", root.join("out/etc-link")).unwrap();
+
+        let tree = scan_path(&root, &root, &ScanOptions::default()).unwrap();
+        for path in [root.clone(), root.join("out"), root.join("out/deep")] {
+            let scanned = PlanEntry::from_scan(&tree, tree.find(&path).unwrap());
+            let measured = measure(&path).unwrap();
+            assert_eq!(measured.entry, scanned, "{}", path.display());
+            assert!(measured.unreadable.is_empty());
+        }
+    }
+
+    #[test]
+    fn save_refuses_paths_that_are_not_utf8() {
+        let temp = tempfile::tempdir().unwrap();
+        let json = temp.path().join("plan.json");
+        let path = temp.path().join(OsStr::from_bytes(b"caf\xe9"));
+        assert!(!is_plannable(&path));
+        let entry = PlanEntry {
+            path,
+            is_dir: false,
+            size: 1,
+            mtime: 0,
+        };
+
+        let error = save(&json, &Plan::new(vec![entry])).unwrap_err();
+        assert!(error.contains("not valid UTF-8"), "{}", error);
+        assert!(!json.exists());
+        assert!(!script_path(&json).exists());
+    }
+
+    #[test]
+    fn save_leaves_an_existing_script_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        let json = temp.path().join("plan.json");
+        let plan = Plan::new(Vec::new());
+
+        let script = save(&json, &plan).unwrap();
+        assert_eq!(script, temp.path().join("plan.sh"));
+        fs::write(&script, "# reviewed\n").unwrap();
+
+        let error = save(&json, &plan).unwrap_err();
+        assert!(error.contains("already exists"), "{}", error);
+        assert_eq!(fs::read_to_string(&script).unwrap(), "# reviewed\n");
+    }
+}
@@ -0,0 +1,444 @@
+//! Which paths may never be deleted
+//!
+//! A policy is a list of path patterns. Each pattern is absolute and matched
+//! component by component:
+//!
+//! - `/var` protects exactly that path
+//! - `/usr/**` protects `/usr` and everything beneath it
+//! - `*` and `?` match within a single component, as in `/home/*/.ssh/**`
+//! - `**` matches any number of components, as in `/**/.git/**`
+//! - a leading `~` stands for the home directory of whoever loads the policy
+//!
+//! A directory that contains a protected path is protected too, since deleting
+//! it would take the protected path with it. That check stops at the first
+//! `**`, so `/**/.git/**` guards every `.git` but not the projects holding them.
+//!
+//! Only std is used here so the privileged helper can share this file.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Built-in rules, always in force
+const DEFAULT_RULES: &[&str] = &[
+    "/",
+    // System trees
+    "/bin/**",
+    "/sbin/**",
+    "/lib/**",
+    "/lib32/**",
+    "/lib64/**",
+    "/libx32/**",
+    "/usr/**",
+    "/etc/**",
+    "/boot/**",
+    "/dev/**",
+    "/proc/**",
+    "/sys/**",
+    "/run/**",
+    "/snap/**",
+    // Package databases, system state and databases under /var/lib; the rest
+    // of it, such as container layers, may be cleared
+    "/var/lib/dpkg/**",
+    "/var/lib/apt/**",
+    "/var/lib/rpm/**",
+    "/var/lib/dnf/**",
+    "/var/lib/pacman/**",
+    "/var/lib/systemd/**",
+    "/var/lib/dbus/**",
+    "/var/lib/mysql/**",
+    "/var/lib/postgresql/**",
+    // Directories whose contents are fair game
+    "/var",
+    "/var/lib",
+    "/var/lib/docker",
+    "/var/lib/containers",
+    "/tmp",
+    "/opt",
+    "/srv",
+    "/mnt",
+    "/media",
+    "/home",
+    // Home directories themselves, and the keys inside them
+    "/root",
+    "/home/*",
+    "/root/.ssh/**",
+    "/root/.gnupg/**",
+    "/home/*/.ssh/**",
+    "/home/*/.gnupg/**",
+];
+
+/// Rules for the home directory of whoever loads the policy, wherever it is
+const HOME_RULES: &[&str] = &["~", "~/.ssh/**", "~/.gnupg/**"];
+
+/// System-wide rules added by the administrator
+pub const SYSTEM_CONFIG: &str = "/etc/scorch/protected.conf";
+
+/// One pattern component
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    /// `**`: any number of components, including none
+    AnyDepth,
+    /// A single component, possibly with `*` and `?` wildcards
+    Name(String),
+}
+
+/// A single protection rule
+#[derive(Debug, Clone)]
+pub struct Rule {
+    /// The pattern as written
+    pub source: String,
+    parts: Vec<Part>,
+}
+
+impl Rule {
+    /// Parse an absolute pattern, expanding a leading `~` to `home`
+    pub fn parse(pattern: &str, home: Option<&Path>) -> Result<Self, String> {
+        let expanded = match pattern.strip_prefix('~') {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                let home = home.ok_or("~ used but there is no home directory")?;
+                format!("{}{}", home.display(), rest)
+            }
+            _ => pattern.to_string(),
+        };
+        if !expanded.starts_with('/') {
+            return Err(format!("'{}' is not an absolute path", pattern));
+        }
+
+        let parts = expanded
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .map(|part| match part {
+                "**" => Part::AnyDepth,
+                name => Part::Name(name.to_string()),
+            })
+            .collect();
+        Ok(Self {
+            source: pattern.to_string(),
+            parts,
+        })
+    }
+
+    /// Whether the rule protects a path given as its normal components
+    fn protects(&self, components: &[&OsStr]) -> bool {
+        matches(&self.parts, components) || contains_match(&self.parts, components)
+    }
+}
+
+/// A set of rules deciding which paths are protected
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Just the built-in rules
+    pub fn defaults() -> Self {
+        let rules = DEFAULT_RULES
+            .iter()
+            .map(|pattern| Rule::parse(pattern, None).expect("built-in rule is valid"))
+            .collect();
+        Self { rules }
+    }
+
+    /// The built-in rules plus the system and user config files
+    ///
+    /// Problems in a config file are reported on stderr; the valid lines
+    /// still apply.
+    pub fn load() -> Self {
+        let mut policy = Self::defaults();
+        let home = env::var_os("HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute());
+        if let Some(home) = &home {
+            policy.rules.extend(
+                HOME_RULES
+                    .iter()
+                    .filter_map(|pattern| Rule::parse(pattern, Some(home)).ok()),
+            );
+        }
+        let configs = [Some(PathBuf::from(SYSTEM_CONFIG)), user_config_path()];
+        for path in configs.into_iter().flatten() {
+            let Ok(text) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let (rules, errors) = parse_config(&text, home.as_deref());
+            for error in errors {
+                eprintln!("scorch: {}: {}", path.display(), error);
+            }
+            policy.rules.extend(rules);
+        }
+        policy
+    }
+
+    /// The first rule protecting `path`, if any
+    pub fn protecting_rule(&self, path: &Path) -> Option<&Rule> {
+        candidates(path).iter().find_map(|candidate| {
+            let components: Vec<&OsStr> = candidate
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                })
+                .collect();
+            self.rules.iter().find(|rule| rule.protects(&components))
+        })
+    }
+
+    pub fn is_protected(&self, path: &Path) -> bool {
+        self.protecting_rule(path).is_some()
+    }
+}
+
+/// The policy in force for this process, loaded on first use
+pub fn global() -> &'static Policy {
+    static POLICY: OnceLock<Policy> = OnceLock::new();
+    POLICY.get_or_init(Policy::load)
+}
+
+/// `$XDG_CONFIG_HOME/scorch/protected.conf`, falling back to `~/.config`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("scorch").join("protected.conf"))
+}
+
+/// Parse a config file: one pattern per line, `#` starts a comment
+pub fn parse_config(text: &str, home: Option<&Path>) -> (Vec<Rule>, Vec<String>) {
+    let mut rules = Vec::new();
+    let mut errors = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        match Rule::parse(line, home) {
+            Ok(rule) => rules.push(rule),
+            Err(e) => errors.push(format!("line {}: {}", number + 1, e)),
+        }
+    }
+    (rules, errors)
+}
+
+/// The forms of `path` to check: as given (made absolute, `..` resolved),
+/// and with its parent's symlinks resolved, so `/lib/x` is also seen as
+/// `/usr/lib/x` on merged-/usr systems
+fn candidates(path: &Path) -> Vec<PathBuf> {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut lexical = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::ParentDir => {
+                lexical.pop();
+            }
+            Component::CurDir => {}
+            other => lexical.push(other),
+        }
+    }
+
+    let mut candidates = vec![lexical.clone()];
+    if let (Some(parent), Some(name)) = (lexical.parent(), lexical.file_name()) {
+        if let Ok(real_parent) = fs::canonicalize(parent) {
+            let real = real_parent.join(name);
+            if real != lexical {
+                candidates.push(real);
+            }
+        }
+    }
+    candidates
+}
+
+/// Whether the pattern matches the whole path
+fn matches(parts: &[Part], components: &[&OsStr]) -> bool {
+    match parts.split_first() {
+        None => components.is_empty(),
+        Some((Part::AnyDepth, rest)) => {
+            (0..=components.len()).any(|skip| matches(rest, &components[skip..]))
+        }
+        Some((Part::Name(pattern), rest)) => match components.split_first() {
+            Some((name, tail)) => {
+                wildcard_match(pattern, &name.to_string_lossy()) && matches(rest, tail)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Whether something beneath the path could match the pattern
+fn contains_match(parts: &[Part], components: &[&OsStr]) -> bool {
+    match (parts.split_first(), components.split_first()) {
+        // The path ran out first: the rest of the pattern lies beneath it
+        (Some(_), None) => true,
+        (None, Some(_)) => false,
+        (None, None) => false,
+        // Don't guess what a ** might reach down to
+        (Some((Part::AnyDepth, _)), Some(_)) => false,
+        (Some((Part::Name(pattern), rest)), Some((name, tail))) => {
+            wildcard_match(pattern, &name.to_string_lossy()) && contains_match(rest, tail)
+        }
+    }
+}
+
+/// Match a single component against a pattern with `*` and `?`
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last * was, and how much text it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last * swallow one more character and retry
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str) -> Rule {
+        Rule::parse(pattern, Some(Path::new("/home/al"))).unwrap()
+    }
+
+    fn components(path: &str) -> Vec<&OsStr> {
+        path.split('/')
+            .filter(|c| !c.is_empty())
+            .map(OsStr::new)
+            .collect()
+    }
+
+    #[test]
+    fn wildcards_match_within_a_component() {
+        let cases = [
+            ("abc", "abc", true),
+            ("abc", "abd", false),
+            ("", "", true),
+            ("", "a", false),
+            ("*", "", true),
+            ("*", "anything", true),
+            ("a*", "abc", true),
+            ("a*c", "abbbc", true),
+            ("a*c", "abd", false),
+            ("*.log", "app.log", true),
+            ("*.log", "app.log.1", false),
+            ("*a*b*", "xaxxbx", true),
+            ("*a*b", "ba", false),
+            ("?", "a", true),
+            ("?", "", false),
+            ("a?c", "abc", true),
+            ("a?c", "ac", false),
+            ("caf?", "café", true),
+            ("??*", "a", false),
+        ];
+        for (pattern, text, expected) in cases {
+            assert_eq!(
+                wildcard_match(pattern, text),
+                expected,
+                "{:?} ~ {:?}",
+                pattern,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn patterns_match_whole_paths() {
+        let cases = [
+            ("/var", "/var", true),
+            ("/var", "/var/log", false),
+            ("/usr/**", "/usr", true),
+            ("/usr/**", "/usr/lib/x", true),
+            ("/usr/**", "/usrx", false),
+            ("/home/*", "/home/al", true),
+            ("/home/*", "/home/al/x", false),
+            ("/home/*/.ssh/**", "/home/al/.ssh/id_ed25519", true),
+            ("/home/*/.ssh/**", "/home/al/x/.ssh", false),
+            ("/**/.git/**", "/.git", true),
+            ("/**/.git/**", "/a/b/.git/objects", true),
+            ("/**/.git/**", "/a/git", false),
+            ("/a/**/b", "/a/b", true),
+            ("/a/**/b", "/a/x/y/b", true),
+            ("/a/**/b", "/a/x/y/c", false),
+            ("/a/?", "/a/bc", false),
+            ("~", "/home/al", true),
+            ("~/.ssh/**", "/home/al/.ssh/config", true),
+            ("~/.ssh/**", "/home/bo/.ssh/config", false),
+        ];
+        for (pattern, path, expected) in cases {
+            let matched = matches(&rule(pattern).parts, &components(path));
+            assert_eq!(matched, expected, "{} ~ {}", pattern, path);
+        }
+    }
+
+    #[test]
+    fn patterns_protect_what_contains_a_match() {
+        let cases = [
+            ("/usr/**", "/", true),
+            ("/home/*/.ssh/**", "/home", true),
+            ("/home/*/.ssh/**", "/home/al", true),
+            ("/home/*/.ssh/**", "/srv", false),
+            ("/var/lib/dpkg/**", "/var/lib", true),
+            ("/var/lib/dpkg/**", "/var/lib/docker", false),
+            ("/var", "/var", false),
+            ("/var", "/var/log", false),
+            // Nothing is guessed past a **
+            ("/**/.git/**", "/src", false),
+            ("/**/.git/**", "/src/project", false),
+            ("~/.ssh/**", "/home", true),
+        ];
+        for (pattern, path, expected) in cases {
+            let contained = contains_match(&rule(pattern).parts, &components(path));
+            assert_eq!(contained, expected, "{} under {}", pattern, path);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_relative_patterns() {
+        assert!(Rule::parse("data/keep", None).is_err());
+        assert!(Rule::parse("~user/keep", None).is_err());
+        assert!(Rule::parse("~/keep", None).is_err());
+        assert!(Rule::parse("~/keep", Some(Path::new("/root"))).is_ok());
+    }
+
+    #[test]
+    fn defaults_leave_container_layers_deletable() {
+        let policy = Policy::defaults();
+        let cases = [
+            ("/", true),
+            ("/usr/lib", true),
+            ("/var/lib", true),
+            ("/var/lib/dpkg/status", true),
+            ("/var/lib/docker", true),
+            ("/var/lib/docker/overlay2/0f3a9c", false),
+            ("/var/lib/containers/storage/overlay/0f3a9c", false),
+            ("/home/al/.ssh", true),
+            ("/home/al/Downloads", false),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(policy.is_protected(Path::new(path)), expected, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Permanent deletion that keeps going past failures
+//!
+//! The privileged helper shares this file, so it sticks to std and libc.
+//! Everything beneath the named path is reached through open directory
+//! descriptors rather than by path, so a directory swapped for a symlink
+//! mid-walk can't send the delete somewhere else.
+
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Something that could not be removed, and why
+#[derive(Debug, Clone)]
+pub struct RemoveFailure {
+    pub path: PathBuf,
+    pub message: String,
+    pub permission_denied: bool,
+}
+
+impl RemoveFailure {
+    fn new(path: &Path, error: &io::Error) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+            permission_denied: error.kind() == io::ErrorKind::PermissionDenied,
+        }
+    }
+}
+
+/// Remove `path` and everything beneath it, returning what couldn't be removed
+///
+/// Unlike `fs::remove_dir_all`, a failure doesn't stop the walk: everything
+/// that can go goes. Symlinks are removed, never followed.
+pub fn remove_tree(path: &Path) -> Vec<RemoveFailure> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        let error = io::Error::new(io::ErrorKind::InvalidInput, "not a removable path");
+        return vec![RemoveFailure::new(path, &error)];
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    match Dir::open(parent) {
+        Ok(dir) => remove_in(&dir, name, path),
+        Err(e) => vec![RemoveFailure::new(path, &e)],
+    }
+}
+
+/// Remove `name` inside the open directory `parent`, and everything beneath it
+///
+/// `path` is where `name` lives, used only to report failures.
+pub fn remove_in(parent: &Dir, name: &OsStr, path: &Path) -> Vec<RemoveFailure> {
+    let mut failures = Vec::new();
+    match CString::new(name.as_bytes()) {
+        Ok(name) => remove_into(parent, &name, path, &mut failures),
+        Err(e) => {
+            let error = io::Error::new(io::ErrorKind::InvalidInput, e);
+            failures.push(RemoveFailure::new(path, &error));
+        }
+    }
+    failures
+}
+
+fn remove_into(parent: &Dir, name: &CStr, path: &Path, failures: &mut Vec<RemoveFailure>) {
+    // Opening with O_NOFOLLOW is the type check: only a real directory opens
+    let dir = match parent.open_child(name) {
+        Ok(dir) => dir,
+        Err(e) if is_not_dir(&e) => {
+            if let Err(e) = parent.unlink(name, 0) {
+                failures.push(RemoveFailure::new(path, &e));
+            }
+            return;
+        }
+        Err(e) => {
+            failures.push(RemoveFailure::new(path, &e));
+            return;
+        }
+    };
+
+    let before = failures.len();
+    match dir.entries() {
+        Ok(names) => {
+            for child in names {
+                let child_path = path.join(OsStr::from_bytes(child.to_bytes()));
+                remove_into(&dir, &child, &child_path, failures);
+            }
+        }
+        Err(e) => {
+            failures.push(RemoveFailure::new(path, &e));
+            return;
+        }
+    }
+    drop(dir);
+
+    // A directory left non-empty by failures inside it needs no extra report
+    if let Err(e) = parent.unlink(name, libc::AT_REMOVEDIR) {
+        if failures.len() == before {
+            failures.push(RemoveFailure::new(path, &e));
+        }
+    }
+}
+
+/// Whether opening as a directory failed because it is something else
+fn is_not_dir(error: &io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::ENOTDIR | libc::ELOOP))
+}
+
+/// An open directory that entries are removed relative to
+pub struct Dir(OwnedFd);
+
+impl Dir {
+    /// Open the directory at `path`, following symlinks like any path lookup
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: path is NUL-terminated and outlives the call
+        let fd = unsafe { libc::open(path.as_ptr(), DIR_FLAGS) };
+        Self::from_result(fd)
+    }
+
+    /// Open the directory `name` inside this one, refusing symlinks
+    fn open_child(&self, name: &CStr) -> io::Result<Self> {
+        // SAFETY: name is NUL-terminated and outlives the call
+        let fd = unsafe {
+            libc::openat(
+                self.0.as_raw_fd(),
+                name.as_ptr(),
+                DIR_FLAGS | libc::O_NOFOLLOW,
+            )
+        };
+        Self::from_result(fd)
+    }
+
+    fn from_result(fd: RawFd) -> io::Result<Self> {
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fd was just opened and nothing else owns it
+        Ok(Self(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    /// Remove `name` inside this directory; `flags` is 0 or `AT_REMOVEDIR`
+    fn unlink(&self, name: &CStr, flags: libc::c_int) -> io::Result<()> {
+        // SAFETY: name is NUL-terminated and outlives the call
+        if unsafe { libc::unlinkat(self.0.as_raw_fd(), name.as_ptr(), flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Names of everything in the directory, apart from `.` and `..`
+    fn entries(&self) -> io::Result<Vec<CString>> {
+        // fdopendir takes over the descriptor it's given, so hand it a copy
+        let copy = self.0.try_clone()?;
+        // SAFETY: copy is an open directory descriptor, owned by the stream
+        // from here on
+        let stream = unsafe { libc::fdopendir(copy.as_raw_fd()) };
+        if stream.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        std::mem::forget(copy);
+
+        let mut names = Vec::new();
+        let result = loop {
+            // SAFETY: errno is thread-local; clearing it tells the end of
+            // the stream apart from an error
+            unsafe { *libc::__errno_location() = 0 };
+            // SAFETY: stream stays open until closedir below
+            let entry = unsafe { libc::readdir(stream) };
+            if entry.is_null() {
+                let error = io::Error::last_os_error();
+                break match error.raw_os_error() {
+                    Some(0) => Ok(()),
+                    _ => Err(error),
+                };
+            }
+            // SAFETY: d_name is NUL-terminated and lives until the next readdir
+            let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) };
+            if name.to_bytes() != b"." && name.to_bytes() != b".." {
+                names.push(name.to_owned());
+            }
+        };
+        // SAFETY: stream came from fdopendir and isn't used again
+        unsafe { libc::closedir(stream) };
+        result.map(|_| names)
+    }
+}
+
+impl AsRawFd for Dir {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+const DIR_FLAGS: libc::c_int = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    #[test]
+    fn removes_a_whole_tree() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join("top.txt"), "x").unwrap();
+        fs::write(root.join("a/b/c/deep.txt"), "x").unwrap();
+
+        assert!(remove_tree(&root).is_empty());
+        assert!(!root.exists());
+    }
+
+    #[test]
+    fn removes_a_single_file() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("file.txt");
+        fs::write(&file, "x").unwrap();
+
+        assert!(remove_tree(&file).is_empty());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn removes_symlinks_without_following_them() {
+        let temp = tempfile::tempdir().unwrap();
+        let outside = temp.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("keep.txt"), "x").unwrap();
+        let root = temp.path().join("root");
+        fs::create_dir(&root).unwrap();
+        symlink(&outside, root.join("link")).unwrap();
+        symlink(&outside, temp.path().join("top-link")).unwrap();
+
+        assert!(remove_tree(&root).is_empty());
+        assert!(!root.exists());
+        assert!(remove_tree(&temp.path().join("top-link")).is_empty());
+        assert!(fs::symlink_metadata(temp.path().join("top-link")).is_err());
+        assert!(outside.join("keep.txt").exists());
+    }
+
+    #[test]
+    fn reports_what_a_read_only_directory_keeps() {
+        // Root can write anywhere, so nothing would be left behind to report
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("root");
+        let locked = root.join("locked");
+        fs::create_dir_all(root.join("open")).unwrap();
+        fs::create_dir(&locked).unwrap();
+        fs::write(root.join("top.txt"), "x").unwrap();
+        fs::write(root.join("open/inner.txt"), "x").unwrap();
+        fs::write(locked.join("a.txt"), "x").unwrap();
+        fs::write(locked.join("b.txt"), "x").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let mut failures = remove_tree(&root);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        failures.sort_by(|a, b| a.path.cmp(&b.path));
+        let paths: Vec<_> = failures.iter().map(|f| f.path.clone()).collect();
+        assert_eq!(paths, [locked.join("a.txt"), locked.join("b.txt")]);
+        assert!(failures.iter().all(|f| f.permission_denied));
+        assert!(!root.join("top.txt").exists());
+        assert!(!root.join("open").exists());
+        assert!(locked.join("a.txt").exists());
+    }
+
+    #[test]
+    fn reports_a_missing_path() {
+        let temp = tempfile::tempdir().unwrap();
+        let failures = remove_tree(&temp.path().join("missing"));
+        assert_eq!(failures.len(), 1);
+        assert!(!failures[0].permission_denied);
+    }
+}
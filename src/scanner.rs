@@ -1,7 +1,14 @@
-use crate::model::DirEntry;
-use std::fs;
-use std::path::PathBuf;
+use crate::model::{DirEntry, SizeMode, Stat};
+use crate::tree::{NodeId, ScanTree};
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use std::collections::HashSet;
+use std::fs::{self, Metadata, ReadDir};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::thread;
 
 /// Virtual filesystems to skip (they don't represent real disk usage)
@@ -14,13 +21,45 @@ const VIRTUAL_FS_PATHS: &[&str] = &[
 ];
 
 /// Check if a path is a virtual filesystem that should be skipped
-fn is_virtual_fs(path: &PathBuf) -> bool {
+pub fn is_virtual_fs(path: &Path) -> bool {
     let path_str = path.to_string_lossy();
     VIRTUAL_FS_PATHS.iter().any(|vfs| {
         path_str == *vfs || path_str.starts_with(&format!("{}/", vfs))
     })
 }
 
+/// Options controlling how a scan walks the filesystem
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Number of worker threads (0 = one per CPU, 1 = serial)
+    pub threads: usize,
+    /// Count every hard link at full size instead of once per inode
+    pub count_hard_links: bool,
+    /// Stop at mount points instead of descending into other filesystems
+    pub one_file_system: bool,
+    /// Stream each top-level directory as soon as it finishes
+    pub live: bool,
+}
+
+/// Shared flag used to abort a running scan
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the scan to stop as soon as possible
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 /// Progress update during scanning
 #[derive(Debug, Clone)]
 pub enum ScanProgress {
@@ -28,70 +67,219 @@ pub enum ScanProgress {
     Scanning(String),
     /// Number of items scanned so far
     ItemCount(usize),
-    /// Scan completed with result
-    Complete(DirEntry),
+    /// A direct child directory of the root finished scanning (live mode)
+    Subtree(ScanTree),
+    /// Scan completed with result; in live mode, everything but the subtrees
+    /// already sent
+    Complete(ScanTree),
+    /// Scan was cancelled; carries the partial tree collected so far, less
+    /// any subtrees already sent
+    Cancelled(ScanTree),
     /// Scan failed with error
     Error(String),
 }
 
 /// Start scanning a directory in a background thread
-pub fn scan_directory(root: PathBuf) -> Receiver<ScanProgress> {
+///
+/// Cancelling `cancel` stops the scan promptly and reports the partial tree.
+pub fn scan_directory(
+    root: PathBuf,
+    options: ScanOptions,
+    cancel: CancelToken,
+) -> Receiver<ScanProgress> {
     let (tx, rx) = mpsc::channel();
 
     thread::spawn(move || {
-        scan_recursive(&root, &tx, &mut 0);
+        // Subdirectories are scanned on a work-stealing pool
+        let pool = match ThreadPoolBuilder::new()
+            .num_threads(options.threads)
+            .build()
+        {
+            Ok(pool) => pool,
+            Err(e) => {
+                let _ = tx.send(ScanProgress::Error(format!(
+                    "Cannot start scan threads: {}",
+                    e
+                )));
+                return;
+            }
+        };
+        let root_dev = if options.one_file_system {
+            fs::metadata(&root).ok().map(|m| m.dev())
+        } else {
+            None
+        };
+        let ctx = ScanContext {
+            tx,
+            count: AtomicUsize::new(0),
+            count_hard_links: options.count_hard_links,
+            root_dev,
+            seen_inodes: Mutex::new(HashSet::new()),
+            cancel,
+            live: options.live,
+        };
+        pool.install(|| scan_root(&root, &ctx));
     });
 
     rx
 }
 
-fn scan_recursive(path: &PathBuf, tx: &Sender<ScanProgress>, count: &mut usize) {
-    // Send progress update
-    *count += 1;
-    if *count % 100 == 0 {
-        let _ = tx.send(ScanProgress::ItemCount(*count));
+/// Scan `path` on the calling thread, for small updates to an existing tree
+///
+/// `root` is the root of the full scan, whose filesystem `one_file_system`
+/// keeps to. Hard links are only recognised within `path` itself.
+pub fn scan_path(path: &Path, root: &Path, options: &ScanOptions) -> Result<ScanTree, String> {
+    let (tx, _rx) = mpsc::channel();
+    let root_dev = if options.one_file_system {
+        fs::metadata(root).ok().map(|m| m.dev())
+    } else {
+        None
+    };
+    let ctx = ScanContext {
+        tx,
+        count: AtomicUsize::new(0),
+        count_hard_links: options.count_hard_links,
+        root_dev,
+        seen_inodes: Mutex::new(HashSet::new()),
+        cancel: CancelToken::new(),
+        live: false,
+    };
+    build_root(&path.to_path_buf(), &ctx)
+}
+
+/// State shared by every worker of a single scan
+struct ScanContext {
+    tx: Sender<ScanProgress>,
+    count: AtomicUsize,
+    count_hard_links: bool,
+    /// Device of the scan root when staying on one filesystem
+    root_dev: Option<u64>,
+    /// (device, inode) of every multiply-linked file seen so far
+    seen_inodes: Mutex<HashSet<(u64, u64)>>,
+    cancel: CancelToken,
+    live: bool,
+}
+
+impl ScanContext {
+    /// Count one scanned item, reporting progress every 100 items
+    fn tick(&self) {
+        let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        if count.is_multiple_of(100) {
+            let _ = self.tx.send(ScanProgress::ItemCount(count));
+        }
     }
-    let _ = tx.send(ScanProgress::Scanning(path.to_string_lossy().to_string()));
 
-    match build_entry(path, tx, count) {
-        Ok(mut entry) => {
-            entry.sort_by_size();
-            let _ = tx.send(ScanProgress::Complete(entry));
+    /// Build a file entry carrying both apparent and allocated sizes
+    fn file_entry(&self, path: PathBuf, metadata: &Metadata) -> DirEntry {
+        // st_blocks is always in 512-byte units, whatever the filesystem block size
+        let mut entry = DirEntry::new_file(path, metadata.len(), metadata.blocks() * 512);
+        entry.nlink = metadata.nlink();
+        entry.stat = Stat::from_metadata(metadata);
+
+        // Only the first link we meet to a shared inode carries its bytes
+        if entry.nlink > 1 && !self.count_hard_links {
+            let key = (metadata.dev(), metadata.ino());
+            let first = self
+                .seen_inodes
+                .lock()
+                .map(|mut seen| seen.insert(key))
+                .unwrap_or(true);
+            if !first {
+                entry.size = 0;
+                entry.disk_size = 0;
+                entry.inodes = 0;
+            }
+        }
+
+        entry
+    }
+}
+
+fn scan_root(path: &PathBuf, ctx: &ScanContext) {
+    // Send progress update
+    ctx.tick();
+    let _ = ctx
+        .tx
+        .send(ScanProgress::Scanning(path.to_string_lossy().to_string()));
+
+    match build_root(path, ctx) {
+        Ok(tree) => {
+            if ctx.cancel.is_cancelled() {
+                let _ = ctx.tx.send(ScanProgress::Cancelled(tree));
+            } else {
+                let _ = ctx.tx.send(ScanProgress::Complete(tree));
+            }
         }
         Err(e) => {
-            let _ = tx.send(ScanProgress::Error(e));
+            let _ = ctx.tx.send(ScanProgress::Error(e));
         }
     }
 }
 
-fn build_entry(
-    path: &PathBuf,
-    tx: &Sender<ScanProgress>,
-    count: &mut usize,
-) -> Result<DirEntry, String> {
+/// Build the scan root, failing if it can't be read at all
+fn build_root(path: &PathBuf, ctx: &ScanContext) -> Result<ScanTree, String> {
     let metadata = fs::metadata(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
 
     if metadata.is_file() {
-        return Ok(DirEntry::new_file(path.clone(), metadata.len()));
+        return Ok(ScanTree::from_entry(
+            ctx.file_entry(path.clone(), &metadata),
+        ));
     }
 
-    let mut entry = DirEntry::new_dir(path.clone());
-
     // Read directory contents
     let read_dir = fs::read_dir(path).map_err(|e| format!("Cannot read directory {}: {}", path.display(), e))?;
 
+    let mut root = DirEntry::new_dir(path.clone());
+    root.stat = Stat::from_metadata(&metadata);
+    let tree = Mutex::new(ScanTree::from_entry(root));
+    let id = lock(&tree).root();
+    build_dir(&tree, id, read_dir, ctx, ctx.live);
+    Ok(tree.into_inner().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// Scan a subdirectory into a tree of its own, to be streamed in live mode
+fn build_fragment(path: &Path, stat: Stat, ctx: &ScanContext) -> ScanTree {
+    let mut root = DirEntry::new_dir(path.to_path_buf());
+    root.stat = stat;
+    let tree = Mutex::new(ScanTree::from_entry(root));
+    let id = lock(&tree).root();
+    build_subdir(&tree, id, path, ctx);
+    tree.into_inner().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Fill in subdirectory `id` (unreadable directories stay empty)
+fn build_subdir(tree: &Mutex<ScanTree>, id: NodeId, path: &Path, ctx: &ScanContext) {
+    if let Ok(read_dir) = fs::read_dir(path) {
+        build_dir(tree, id, read_dir, ctx, false);
+    }
+}
+
+/// Read directory `id` into the tree and recurse into its subdirectories
+///
+/// With `stream` set, each subdirectory is scanned into a tree of its own
+/// and sent as a `Subtree` once it finishes, rather than kept here.
+fn build_dir(
+    tree: &Mutex<ScanTree>,
+    id: NodeId,
+    read_dir: ReadDir,
+    ctx: &ScanContext,
+    stream: bool,
+) {
+    let mut entries = Vec::new();
+    let mut subdirs = Vec::new();
+
     for item in read_dir {
+        if ctx.cancel.is_cancelled() {
+            break;
+        }
+
         let item = match item {
             Ok(i) => i,
             Err(_) => continue, // Skip entries we can't read
         };
 
         let item_path = item.path();
-        *count += 1;
-
-        if *count % 100 == 0 {
-            let _ = tx.send(ScanProgress::ItemCount(*count));
-        }
+        ctx.tick();
 
         // Get metadata (don't follow symlinks)
         let item_metadata = match fs::symlink_metadata(&item_path) {
@@ -105,79 +293,233 @@ fn build_entry(
         }
 
         if item_metadata.is_file() {
-            entry.children.push(DirEntry::new_file(item_path, item_metadata.len()));
+            entries.push(ctx.file_entry(item_path, &item_metadata));
         } else if item_metadata.is_dir() {
             // Skip virtual filesystems
             if is_virtual_fs(&item_path) {
                 continue;
             }
-            // Recursively scan subdirectory
-            match build_entry_quiet(&item_path, count) {
-                Ok(child) => entry.children.push(child),
-                Err(_) => continue, // Skip directories we can't read
+            // Leave a marker instead of crossing into another filesystem
+            let stat = Stat::from_metadata(&item_metadata);
+            if ctx.root_dev.is_some_and(|dev| dev != item_metadata.dev()) {
+                let mut mount = DirEntry::new_mount_point(item_path);
+                mount.stat = stat;
+                entries.push(mount);
+                continue;
             }
+            subdirs.push((item_path, stat));
         }
     }
 
-    // Calculate size from children
-    entry.size = entry.children.iter().map(|c| c.total_size()).sum();
+    // One lock per directory rather than one per entry
+    let mut guard = lock(tree);
+    for entry in entries {
+        guard.add_child(id, entry);
+    }
 
-    Ok(entry)
-}
+    if stream {
+        drop(guard);
+        subdirs.par_iter().for_each(|(p, stat)| {
+            let fragment = build_fragment(p, *stat, ctx);
+            if ctx.cancel.is_cancelled() {
+                // Kept for the partial tree instead
+                lock(tree).graft(id, fragment);
+            } else {
+                let _ = ctx.tx.send(ScanProgress::Subtree(fragment));
+            }
+        });
+    } else {
+        let children: Vec<_> = subdirs
+            .into_iter()
+            .map(|(p, stat)| {
+                let mut dir = DirEntry::new_dir(p.clone());
+                dir.stat = stat;
+                (guard.add_child(id, dir), p)
+            })
+            .collect();
+        drop(guard);
 
-/// Build entry without sending progress (for recursive calls)
-fn build_entry_quiet(path: &PathBuf, count: &mut usize) -> Result<DirEntry, String> {
-    // Skip virtual filesystems
-    if is_virtual_fs(path) {
-        return Ok(DirEntry::new_dir(path.clone()));
+        // Recursively scan subdirectories in parallel
+        children
+            .par_iter()
+            .for_each(|(child, p)| build_subdir(tree, *child, p, ctx));
     }
-    let metadata = fs::symlink_metadata(path)
-        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
 
-    if metadata.is_file() {
-        return Ok(DirEntry::new_file(path.clone(), metadata.len()));
-    }
+    // Calculate totals from children
+    lock(tree).finish_dir(id, SizeMode::Apparent);
+}
 
-    let mut entry = DirEntry::new_dir(path.clone());
+/// The tree being built, whatever a panicking worker left it as
+fn lock(tree: &Mutex<ScanTree>) -> MutexGuard<'_, ScanTree> {
+    tree.lock().unwrap_or_else(PoisonError::into_inner)
+}
 
-    let read_dir = match fs::read_dir(path) {
-        Ok(rd) => rd,
-        Err(_) => return Ok(entry), // Return empty dir if unreadable
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
 
-    for item in read_dir {
-        let item = match item {
-            Ok(i) => i,
-            Err(_) => continue,
-        };
+    /// A tree of a few hundred files, with a file hard-linked into two
+    /// folders; returns it with the bytes it holds
+    fn sample_tree() -> (tempfile::TempDir, u64) {
+        let temp = tempfile::tempdir().unwrap();
+        let mut bytes = 50_000;
+        for d in 0..6 {
+            for s in 0..4 {
+                let dir = temp.path().join(format!("dir{}/sub{}", d, s));
+                fs::create_dir_all(&dir).unwrap();
+                for f in 0..10 {
+                    let len = (d * 1000 + s * 100 + f) * 37;
+                    bytes += len as u64;
+                    fs::write(dir.join(format!("file{}", f)), vec![0u8; len]).unwrap();
+                }
+            }
+        }
+        fs::create_dir(temp.path().join("empty")).unwrap();
+        fs::write(temp.path().join("shared"), vec![0u8; 50_000]).unwrap();
+        fs::hard_link(temp.path().join("shared"), temp.path().join("dir0/link")).unwrap();
+        fs::hard_link(
+            temp.path().join("shared"),
+            temp.path().join("dir5/sub3/link"),
+        )
+        .unwrap();
+        symlink(temp.path().join("dir1"), temp.path().join("dir2/loop")).unwrap();
+        (temp, bytes)
+    }
 
-        let item_path = item.path();
-        *count += 1;
+    /// Run a full scan and wait for its result
+    fn scan(path: &Path, options: ScanOptions) -> ScanTree {
+        let rx = scan_directory(path.to_path_buf(), options, CancelToken::new());
+        for progress in rx {
+            match progress {
+                ScanProgress::Complete(tree) => return tree,
+                ScanProgress::Error(e) => panic!("scan failed: {}", e),
+                _ => {}
+            }
+        }
+        panic!("scan ended without a result");
+    }
 
-        let item_metadata = match fs::symlink_metadata(&item_path) {
-            Ok(m) => m,
-            Err(_) => continue,
-        };
+    fn totals(tree: &ScanTree) -> (u64, u64, u64, u64, u64) {
+        let root = tree.get(tree.root());
+        (
+            root.size,
+            root.disk_size,
+            root.files,
+            root.dirs,
+            root.inodes,
+        )
+    }
 
-        if item_metadata.is_symlink() {
-            continue;
+    #[test]
+    fn parallel_scan_matches_serial_scan() {
+        let (temp, bytes) = sample_tree();
+        for one_file_system in [false, true] {
+            let options = |threads| ScanOptions {
+                threads,
+                one_file_system,
+                ..Default::default()
+            };
+            let serial = scan(temp.path(), options(1));
+            let parallel = scan(temp.path(), options(8));
+
+            assert_eq!(totals(&parallel), totals(&serial));
+            assert!(parallel.skipped_mount_points().is_empty());
+            let (size, _, files, _, inodes) = totals(&serial);
+            // 240 files plus three names for the shared one, whose inode counts once
+            assert_eq!(files, 243);
+            assert_eq!(inodes, 1 + 6 + 6 * 4 + 1 + 241);
+            assert_eq!(size, bytes);
         }
+    }
 
-        if item_metadata.is_file() {
-            entry.children.push(DirEntry::new_file(item_path, item_metadata.len()));
-        } else if item_metadata.is_dir() {
-            // Skip virtual filesystems
-            if is_virtual_fs(&item_path) {
-                continue;
-            }
-            match build_entry_quiet(&item_path, count) {
-                Ok(child) => entry.children.push(child),
-                Err(_) => continue,
+    #[test]
+    fn hard_links_count_in_full_when_asked() {
+        let (temp, _) = sample_tree();
+        let options = ScanOptions {
+            count_hard_links: true,
+            ..Default::default()
+        };
+        let (size, _, _, _, inodes) = totals(&scan(temp.path(), ScanOptions::default()));
+        let counted = totals(&scan(temp.path(), options));
+        assert_eq!(counted.0, size + 2 * 50_000);
+        assert_eq!(counted.4, inodes + 2);
+    }
+
+    #[test]
+    fn live_scan_streams_each_top_level_directory_once() {
+        let (temp, _) = sample_tree();
+        let options = ScanOptions {
+            threads: 8,
+            live: true,
+            ..Default::default()
+        };
+        let rx = scan_directory(temp.path().to_path_buf(), options, CancelToken::new());
+        let mut streamed: Option<ScanTree> = None;
+        let mut names = Vec::new();
+        for progress in rx {
+            match progress {
+                ScanProgress::Subtree(subtree) => {
+                    let name = subtree.root_path().file_name().unwrap();
+                    names.push(name.to_string_lossy().into_owned());
+                    let root = DirEntry::new_dir(temp.path().to_path_buf());
+                    let tree = streamed.get_or_insert_with(|| ScanTree::from_entry(root));
+                    assert!(tree.insert_tree(subtree, SizeMode::Apparent));
+                }
+                ScanProgress::Complete(rest) => {
+                    // Only the files beside the streamed directories are left
+                    assert_eq!(totals(&rest).2, 1);
+                    let mut tree = streamed.take().unwrap();
+                    tree.absorb(rest, SizeMode::Apparent);
+                    streamed = Some(tree);
+                }
+                ScanProgress::Error(e) => panic!("scan failed: {}", e),
+                _ => {}
             }
         }
+
+        names.sort();
+        assert_eq!(
+            names,
+            ["dir0", "dir1", "dir2", "dir3", "dir4", "dir5", "empty"]
+        );
+        let full = scan(temp.path(), ScanOptions::default());
+        assert_eq!(totals(&streamed.unwrap()), totals(&full));
     }
 
-    entry.size = entry.children.iter().map(|c| c.total_size()).sum();
+    #[test]
+    fn one_file_system_stops_at_other_devices() {
+        let (temp, _) = sample_tree();
+        let root = temp.path().to_path_buf();
+        for threads in [1, 8] {
+            let (tx, _rx) = mpsc::channel();
+            let dev = fs::metadata(&root).unwrap().dev();
+            // Pretend the root lives elsewhere, so every directory is a mount point
+            let ctx = ScanContext {
+                tx,
+                count: AtomicUsize::new(0),
+                count_hard_links: false,
+                root_dev: Some(dev.wrapping_add(1)),
+                seen_inodes: Mutex::new(HashSet::new()),
+                cancel: CancelToken::new(),
+                live: false,
+            };
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let tree = pool.install(|| build_root(&root, &ctx)).unwrap();
 
-    Ok(entry)
+            let mut skipped = tree.skipped_mount_points();
+            skipped.sort();
+            let names: Vec<_> = skipped.iter().filter_map(|p| p.file_name()).collect();
+            assert_eq!(
+                names,
+                ["dir0", "dir1", "dir2", "dir3", "dir4", "dir5", "empty"]
+            );
+            let (size, _, files, _, _) = totals(&tree);
+            assert_eq!(files, 1);
+            assert_eq!(size, 50_000);
+        }
+    }
 }
@@ -0,0 +1,495 @@
+use crate::model::{DirEntry, SizeMode, Stat};
+use crate::tree::{NodeId, ScanTree};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Magic bytes at the start of every snapshot file
+const MAGIC: &[u8; 8] = b"SCORCHSN";
+
+/// Current snapshot format version
+const VERSION: u8 = 2;
+
+/// Oldest version that can still be read; it has no timestamps or owners
+const MIN_VERSION: u8 = 1;
+
+/// Deepest nesting a snapshot may have, far beyond any real tree, so a
+/// damaged file can't recurse until the stack runs out
+const MAX_DEPTH: usize = 512;
+
+/// Default file extension for snapshots
+pub const EXTENSION: &str = "scorch";
+
+const FLAG_FILE: u8 = 1 << 0;
+const FLAG_MOUNT_POINT: u8 = 1 << 1;
+/// A hard link whose inode was counted under another path
+const FLAG_COUNTED_ELSEWHERE: u8 = 1 << 2;
+
+/// Where and when a snapshot was taken
+#[derive(Debug, Clone)]
+pub struct SnapshotInfo {
+    /// Path that was scanned
+    pub root: PathBuf,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Host the scan ran on
+    pub hostname: String,
+    /// Size mode in use when the snapshot was saved
+    pub size_mode: SizeMode,
+}
+
+impl SnapshotInfo {
+    /// Describe a scan of `root` taken now on this host
+    pub fn new(root: PathBuf, size_mode: SizeMode) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            root,
+            timestamp,
+            hostname: hostname(),
+            size_mode,
+        }
+    }
+}
+
+/// Read this machine's hostname from procfs, falling back to `/etc/hostname`
+pub fn hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .filter_map(|p| fs::read_to_string(p).ok())
+        .map(|s| s.trim().to_string())
+        .find(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Write a scan tree and its metadata to `path`
+pub fn save(path: &Path, tree: &ScanTree, info: &SnapshotInfo) -> Result<(), String> {
+    let write = || -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        write_bytes(&mut out, info.root.as_os_str().as_bytes())?;
+        write_varint(&mut out, info.timestamp)?;
+        write_str(&mut out, &info.hostname)?;
+        out.write_all(&[match info.size_mode {
+            SizeMode::Apparent => 0,
+            SizeMode::Allocated => 1,
+            SizeMode::Inodes => 2,
+        }])?;
+        write_entry(&mut out, tree, tree.root())?;
+        out.flush()
+    };
+    write().map_err(|e| format!("Cannot write snapshot {}: {}", path.display(), e))
+}
+
+/// Read a snapshot written by `save`
+pub fn load(path: &Path) -> Result<(SnapshotInfo, ScanTree), String> {
+    let read = || -> io::Result<(SnapshotInfo, ScanTree)> {
+        let mut input = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a scorch snapshot"));
+        }
+        let version = read_u8(&mut input)?;
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(invalid(&format!(
+                "unsupported snapshot version {}",
+                version
+            )));
+        }
+
+        let root = PathBuf::from(OsString::from_vec(read_bytes(&mut input)?));
+        let timestamp = read_varint(&mut input)?;
+        let hostname = read_str(&mut input)?;
+        let size_mode = match read_u8(&mut input)? {
+            0 => SizeMode::Apparent,
+            1 => SizeMode::Allocated,
+            2 => SizeMode::Inodes,
+            other => return Err(invalid(&format!("unknown size mode {}", other))),
+        };
+
+        let has_stat = version >= 2;
+        let entry = read_entry(&mut input, &root, 0, has_stat)?;
+        let is_file = entry.is_file;
+        let mut tree = ScanTree::from_entry(entry);
+        if !is_file {
+            let id = tree.root();
+            read_children(&mut input, &mut tree, id, &root, 1, has_stat)?;
+        }
+        let info = SnapshotInfo {
+            root,
+            timestamp,
+            hostname,
+            size_mode,
+        };
+        Ok((info, tree))
+    };
+    read().map_err(|e| format!("Cannot read snapshot {}: {}", path.display(), e))
+}
+
+// Each entry is written pre-order as: flags, name, stat, then either the
+// file's sizes and link count, or the directory's child count followed by its
+// children. Directory totals are recomputed on load rather than stored. Names
+// are the raw bytes from the filesystem, so they needn't be UTF-8.
+
+fn write_entry(out: &mut impl Write, tree: &ScanTree, id: NodeId) -> io::Result<()> {
+    let node = tree.get(id);
+    let mut flags = 0;
+    if node.is_file {
+        flags |= FLAG_FILE;
+    }
+    if node.mount_point {
+        flags |= FLAG_MOUNT_POINT;
+    }
+    if node.is_file && node.inodes == 0 {
+        flags |= FLAG_COUNTED_ELSEWHERE;
+    }
+    out.write_all(&[flags])?;
+    write_bytes(out, tree.file_name(id).as_bytes())?;
+    write_stat(out, &node.stat)?;
+
+    if node.is_file {
+        write_varint(out, node.size)?;
+        write_varint(out, node.disk_size)?;
+        write_varint(out, node.nlink)?;
+    } else {
+        write_varint(out, node.children.len() as u64)?;
+        for &child in &node.children {
+            write_entry(out, tree, child)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read one entry `depth` levels down, leaving a directory's children to
+/// `read_children`; the root takes `base` as its path, others join their
+/// name onto it
+///
+/// Entries from before `has_stat` was added to the format get a zeroed `Stat`.
+fn read_entry(
+    input: &mut impl Read,
+    base: &Path,
+    depth: usize,
+    has_stat: bool,
+) -> io::Result<DirEntry> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("entries nested too deeply"));
+    }
+    let flags = read_u8(input)?;
+    let name = OsString::from_vec(read_bytes(input)?);
+    let stat = if has_stat {
+        read_stat(input)?
+    } else {
+        Stat::default()
+    };
+    let path = if depth == 0 {
+        base.to_path_buf()
+    } else {
+        // Anything but a single component would put the entry somewhere else
+        let bytes = name.as_bytes();
+        if bytes.is_empty() || bytes.contains(&b'/') || bytes == b"." || bytes == b".." {
+            return Err(invalid("bad entry name"));
+        }
+        base.join(&name)
+    };
+
+    if flags & FLAG_FILE != 0 {
+        let size = read_varint(input)?;
+        let disk_size = read_varint(input)?;
+        let mut entry = DirEntry::new_file(path, size, disk_size);
+        entry.stat = stat;
+        entry.nlink = read_varint(input)?;
+        if flags & FLAG_COUNTED_ELSEWHERE != 0 {
+            entry.inodes = 0;
+        }
+        return Ok(entry);
+    }
+
+    let mut entry = if flags & FLAG_MOUNT_POINT != 0 {
+        DirEntry::new_mount_point(path)
+    } else {
+        DirEntry::new_dir(path)
+    };
+    entry.stat = stat;
+    Ok(entry)
+}
+
+/// Read the children of directory `id`, at `path`, straight into the tree
+fn read_children(
+    input: &mut impl Read,
+    tree: &mut ScanTree,
+    id: NodeId,
+    path: &Path,
+    depth: usize,
+    has_stat: bool,
+) -> io::Result<()> {
+    let count = read_varint(input)?;
+    for _ in 0..count {
+        let child = read_entry(input, path, depth, has_stat)?;
+        if child.is_file {
+            tree.add_child(id, child);
+        } else {
+            let child_path = child.path.clone();
+            let child = tree.add_child(id, child);
+            read_children(input, tree, child, &child_path, depth + 1, has_stat)?;
+        }
+    }
+    tree.finish_dir(id, SizeMode::Apparent);
+    Ok(())
+}
+
+// Times before 1970 are rare enough that storing them as 10-byte varints is fine
+
+fn write_stat(out: &mut impl Write, stat: &Stat) -> io::Result<()> {
+    write_varint(out, stat.mtime as u64)?;
+    write_varint(out, stat.atime as u64)?;
+    write_varint(out, stat.ctime as u64)?;
+    write_varint(out, u64::from(stat.uid))?;
+    write_varint(out, u64::from(stat.gid))?;
+    write_varint(out, u64::from(stat.mode))
+}
+
+fn read_stat(input: &mut impl Read) -> io::Result<Stat> {
+    Ok(Stat {
+        mtime: read_varint(input)? as i64,
+        atime: read_varint(input)? as i64,
+        ctime: read_varint(input)? as i64,
+        uid: read_varint(input)? as u32,
+        gid: read_varint(input)? as u32,
+        mode: read_varint(input)? as u32,
+    })
+}
+
+fn write_varint(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(input: &mut impl Read) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(input)?;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint too long"))
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    write_varint(out, bytes.len() as u64)?;
+    out.write_all(bytes)
+}
+
+fn read_bytes(input: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_varint(input)?;
+    // The length comes from the file: read what's there rather than trusting it
+    let mut buf = Vec::new();
+    input.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+fn write_str(out: &mut impl Write, s: &str) -> io::Result<()> {
+    write_bytes(out, s.as_bytes())
+}
+
+fn read_str(input: &mut impl Read) -> io::Result<String> {
+    String::from_utf8(read_bytes(input)?).map_err(|_| invalid("text is not valid UTF-8"))
+}
+
+fn read_u8(input: &mut impl Read) -> io::Result<u8> {
+    let mut byte = [0u8; 1];
+    input.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn stat(mtime: i64, uid: u32, mode: u32) -> Stat {
+        Stat {
+            mtime,
+            atime: mtime + 10,
+            ctime: mtime + 20,
+            uid,
+            gid: uid + 1,
+            mode,
+        }
+    }
+
+    /// A small tree with a non-UTF-8 name, a hard link counted elsewhere and
+    /// a mount point
+    fn sample() -> (SnapshotInfo, DirEntry) {
+        let base = PathBuf::from("/data");
+        let mut report = DirEntry::new_file(base.join("docs/report.pdf"), 1234, 4096);
+        report.stat = stat(1_700_000_000, 1000, 0o100644);
+        let odd_path = base.join("docs").join(OsStr::from_bytes(b"caf\xe9.txt"));
+        let mut odd = DirEntry::new_file(odd_path, 10, 4096);
+        odd.stat = stat(-86_400, 0, 0o100600);
+        let mut docs = DirEntry::new_dir(base.join("docs"));
+        docs.stat = stat(1_600_000_000, 1000, 0o40755);
+        docs.children = vec![report, odd];
+        docs.update_totals();
+
+        let mut link = DirEntry::new_file(base.join("link"), 0, 0);
+        link.nlink = 2;
+        link.inodes = 0;
+        let mount = DirEntry::new_mount_point(base.join("mnt"));
+
+        let mut root = DirEntry::new_dir(base.clone());
+        root.stat = stat(1_500_000_000, 0, 0o40755);
+        root.children = vec![docs, link, mount];
+        root.update_totals();
+
+        let info = SnapshotInfo {
+            root: base,
+            timestamp: 1_700_000_500,
+            hostname: "box".to_string(),
+            size_mode: SizeMode::Allocated,
+        };
+        (info, root)
+    }
+
+    fn assert_same(a: &ScanTree, a_id: NodeId, b: &ScanTree, b_id: NodeId) {
+        assert_eq!(a.path(a_id), b.path(b_id));
+        let (a_node, b_node) = (a.get(a_id), b.get(b_id));
+        assert_eq!(
+            (a_node.size, a_node.disk_size, a_node.files, a_node.dirs),
+            (b_node.size, b_node.disk_size, b_node.files, b_node.dirs)
+        );
+        assert_eq!((a_node.inodes, a_node.nlink), (b_node.inodes, b_node.nlink));
+        assert_eq!(
+            (a_node.is_file, a_node.mount_point),
+            (b_node.is_file, b_node.mount_point)
+        );
+        assert_eq!(a_node.stat, b_node.stat);
+        assert_eq!(a_node.children.len(), b_node.children.len());
+        for (&a_child, &b_child) in a_node.children.iter().zip(&b_node.children) {
+            assert_same(a, a_child, b, b_child);
+        }
+    }
+
+    /// Write raw snapshot bytes to a temp file and try to load them
+    fn load_bytes(bytes: &[u8]) -> Result<(SnapshotInfo, ScanTree), String> {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("snap.scorch");
+        fs::write(&path, bytes).unwrap();
+        load(&path)
+    }
+
+    /// The header of a version 2 snapshot of `/data`
+    fn header() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        write_bytes(&mut out, b"/data").unwrap();
+        write_varint(&mut out, 0).unwrap();
+        write_str(&mut out, "box").unwrap();
+        out.push(0);
+        out
+    }
+
+    #[test]
+    fn round_trips_a_tree() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("snap.scorch");
+        let (info, root) = sample();
+        let tree = ScanTree::from_entry(root);
+        save(&path, &tree, &info).unwrap();
+
+        let (loaded_info, loaded) = load(&path).unwrap();
+        assert_eq!(loaded_info.root, info.root);
+        assert_eq!(loaded_info.timestamp, info.timestamp);
+        assert_eq!(loaded_info.hostname, info.hostname);
+        assert_eq!(loaded_info.size_mode, info.size_mode);
+        assert_same(&loaded, loaded.root(), &tree, tree.root());
+        let odd = PathBuf::from("/data/docs").join(OsStr::from_bytes(b"caf\xe9.txt"));
+        assert!(loaded.contains(&odd));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("snap.scorch");
+        let (info, root) = sample();
+        save(&path, &ScanTree::from_entry(root), &info).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        for len in 0..bytes.len() {
+            assert!(load_bytes(&bytes[..len]).is_err(), "loaded {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut bytes = header();
+        bytes[..8].copy_from_slice(b"NOTSCRCH");
+        let error = load_bytes(&bytes).unwrap_err();
+        assert!(error.contains("not a scorch snapshot"), "{}", error);
+
+        let mut bytes = header();
+        bytes[8] = VERSION + 1;
+        let error = load_bytes(&bytes).unwrap_err();
+        assert!(error.contains("unsupported snapshot version"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_lengths_the_file_cannot_hold() {
+        let mut bytes = header();
+        bytes.push(0);
+        write_varint(&mut bytes, u64::MAX).unwrap();
+        bytes.extend_from_slice(b"short");
+        assert!(load_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_names_that_leave_their_directory() {
+        for name in [&b".."[..], b"a/b", b""] {
+            let mut bytes = header();
+            for entry_name in [&b"data"[..], name] {
+                bytes.push(0);
+                write_bytes(&mut bytes, entry_name).unwrap();
+                write_stat(&mut bytes, &Stat::default()).unwrap();
+                write_varint(&mut bytes, 1).unwrap();
+            }
+            let error = load_bytes(&bytes).unwrap_err();
+            assert!(error.contains("bad entry name"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn rejects_runaway_nesting() {
+        let mut bytes = header();
+        for _ in 0..=MAX_DEPTH + 1 {
+            bytes.push(0);
+            write_bytes(&mut bytes, b"d").unwrap();
+            write_stat(&mut bytes, &Stat::default()).unwrap();
+            write_varint(&mut bytes, 1).unwrap();
+        }
+        let error = load_bytes(&bytes).unwrap_err();
+        assert!(error.contains("nested too deeply"), "{}", error);
+    }
+}
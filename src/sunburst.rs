@@ -1,7 +1,10 @@
-use crate::model::{format_size, DirEntry, FileType};
+use crate::diff::Change;
+use crate::model::{format_date, FileType, SizeMode, Stat};
+use crate::tree::{NodeId, ScanTree};
 use gtk4::cairo::{Context, FontSlant, FontWeight};
 use std::f64::consts::PI;
 use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Maximum depth of rings to display
 pub const MAX_DEPTH: usize = 5;
@@ -9,117 +12,203 @@ pub const MAX_DEPTH: usize = 5;
 /// Minimum angle (radians) for a segment to be rendered
 const MIN_ANGLE: f64 = 0.01;
 
+/// What segment colours represent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// Colour by file type
+    #[default]
+    FileType,
+    /// Colour by growth since the comparison baseline
+    Growth,
+    /// Colour by how recently anything beneath was modified
+    Newest,
+    /// Colour by how long the oldest file beneath has gone unmodified
+    Oldest,
+    /// Colour by the user that owns each entry
+    Owner,
+}
+
+const DAY: i64 = 86_400;
+
+/// Age buckets, hottest first: how far back each reaches and what to call it
+const AGE_BUCKETS: [(i64, &str); 6] = [
+    (DAY, "Past day"),
+    (7 * DAY, "Past week"),
+    (30 * DAY, "Past month"),
+    (182 * DAY, "Past 6 months"),
+    (365 * DAY, "Past year"),
+    (730 * DAY, "Past 2 years"),
+];
+
+/// One colour per bucket plus one for anything older, from white-hot to ice
+const AGE_COLORS: [(f64, f64, f64); 7] = [
+    (1.0, 0.95, 0.6),
+    (1.0, 0.75, 0.2),
+    (1.0, 0.45, 0.1),
+    (0.8, 0.2, 0.1),
+    (0.5, 0.2, 0.35),
+    (0.3, 0.3, 0.6),
+    (0.2, 0.45, 0.85),
+];
+
+/// Entries with no dated files beneath, e.g. from an old snapshot
+const UNDATED_COLOR: (f64, f64, f64) = (0.35, 0.32, 0.32);
+
+/// One colour for each of the largest owners in view, largest first
+pub const OWNER_COLORS: [(f64, f64, f64); 8] = [
+    (1.0, 0.45, 0.1),
+    (0.25, 0.6, 0.95),
+    (0.95, 0.8, 0.2),
+    (0.45, 0.8, 0.35),
+    (0.8, 0.35, 0.75),
+    (0.3, 0.8, 0.8),
+    (0.95, 0.55, 0.6),
+    (0.6, 0.45, 0.3),
+];
+
+/// Everyone past the owners that got a colour of their own
+const OTHER_OWNER_COLOR: (f64, f64, f64) = (0.35, 0.32, 0.32);
+
 /// A segment in the sunburst chart
 #[derive(Debug, Clone)]
 pub struct Segment {
     pub path: PathBuf,
     pub name: String,
+    /// Size measured the way the arcs are
     pub size: u64,
     pub file_type: FileType,
     pub depth: usize,
     pub start_angle: f64,
     pub end_angle: f64,
     pub is_file: bool,
+    /// Hard link count (files only)
+    pub nlink: u64,
+    /// Files beneath a directory
+    pub files: u64,
+    /// Directories beneath a directory, itself included
+    pub dirs: u64,
+    pub stat: Stat,
+    /// Modification times of the oldest and newest file beneath
+    pub mtimes: Option<(i64, i64)>,
+    /// Index into `OWNER_COLORS` of this entry's owner, in owner colours
+    pub owner: Option<usize>,
+    /// How this entry changed since the baseline, and by how much
+    pub change: Option<(Change, i64)>,
+    /// Marked for deletion, itself or through a parent
+    pub marked: bool,
 }
 
-impl Segment {
-    /// Check if a point (in polar coords) is inside this segment
-    pub fn contains_point(&self, angle: f64, radius_depth: usize) -> bool {
-        if radius_depth != self.depth {
-            return false;
-        }
-        // Normalize angle to [0, 2*PI)
-        let mut a = angle;
-        while a < 0.0 {
-            a += 2.0 * PI;
-        }
-        while a >= 2.0 * PI {
-            a -= 2.0 * PI;
-        }
-        a >= self.start_angle && a < self.end_angle
-    }
-}
-
-/// Build segments from a DirEntry tree
-pub fn build_segments(root: &DirEntry, max_depth: usize) -> Vec<Segment> {
-    let mut segments = Vec::new();
-    let total_size = root.total_size();
+/// Build segments for the subtree at `id`, sizing arcs by `mode`
+pub fn build_segments(
+    tree: &ScanTree,
+    id: NodeId,
+    max_depth: usize,
+    mode: SizeMode,
+) -> Vec<Segment> {
+    let root = tree.get(id);
+    let total_size = root.size_in(mode);
     if total_size == 0 {
-        return segments;
+        return Vec::new();
     }
 
+    let mut builder = SegmentBuilder {
+        tree,
+        max_depth,
+        mode,
+        segments: Vec::new(),
+    };
+
     // Add center segment for root
-    segments.push(Segment {
-        path: root.path.clone(),
-        name: root.name.clone(),
+    builder.segments.push(Segment {
+        path: tree.path(id),
+        name: tree.name(id).into_owned(),
         size: total_size,
         file_type: root.file_type,
         depth: 0,
         start_angle: 0.0,
         end_angle: 2.0 * PI,
         is_file: root.is_file,
+        nlink: root.nlink,
+        files: root.files,
+        dirs: root.dirs,
+        stat: root.stat,
+        mtimes: root.mtimes(),
+        owner: None,
+        change: None,
+        marked: false,
     });
 
     // Build child segments recursively
-    build_segments_recursive(root, 1, 0.0, 2.0 * PI, total_size, max_depth, &mut segments);
+    builder.add_children(id, 1, 0.0, 2.0 * PI, total_size);
 
-    segments
+    builder.segments
 }
 
-fn build_segments_recursive(
-    entry: &DirEntry,
-    depth: usize,
-    start_angle: f64,
-    end_angle: f64,
-    total_size: u64,
+/// Accumulates segments while walking the tree
+struct SegmentBuilder<'a> {
+    tree: &'a ScanTree,
     max_depth: usize,
-    segments: &mut Vec<Segment>,
-) {
-    if depth > max_depth {
-        return;
-    }
-
-    let angle_range = end_angle - start_angle;
-    let mut current_angle = start_angle;
-
-    for child in &entry.children {
-        let child_size = child.total_size();
-        if child_size == 0 {
-            continue;
-        }
+    mode: SizeMode,
+    segments: Vec<Segment>,
+}
 
-        let child_angle = (child_size as f64 / total_size as f64) * angle_range;
-        if child_angle < MIN_ANGLE {
-            continue; // Skip tiny segments
+impl SegmentBuilder<'_> {
+    fn add_children(
+        &mut self,
+        id: NodeId,
+        depth: usize,
+        start_angle: f64,
+        end_angle: f64,
+        total_size: u64,
+    ) {
+        if depth > self.max_depth {
+            return;
         }
 
-        let child_end = current_angle + child_angle;
-
-        segments.push(Segment {
-            path: child.path.clone(),
-            name: child.name.clone(),
-            size: child_size,
-            file_type: child.file_type,
-            depth,
-            start_angle: current_angle,
-            end_angle: child_end,
-            is_file: child.is_file,
-        });
-
-        // Recurse into directories
-        if !child.is_file && !child.children.is_empty() {
-            build_segments_recursive(
-                child,
-                depth + 1,
-                current_angle,
-                child_end,
-                child_size,
-                max_depth,
-                segments,
-            );
+        let angle_range = end_angle - start_angle;
+        let mut current_angle = start_angle;
+
+        let tree = self.tree;
+        for &child_id in &tree.get(id).children {
+            let child = tree.get(child_id);
+            let child_size = child.size_in(self.mode);
+            if child_size == 0 {
+                continue;
+            }
+
+            let child_angle = (child_size as f64 / total_size as f64) * angle_range;
+            if child_angle < MIN_ANGLE {
+                continue; // Skip tiny segments
+            }
+
+            let child_end = current_angle + child_angle;
+
+            self.segments.push(Segment {
+                path: tree.path(child_id),
+                name: tree.name(child_id).into_owned(),
+                size: child_size,
+                file_type: child.file_type,
+                depth,
+                start_angle: current_angle,
+                end_angle: child_end,
+                is_file: child.is_file,
+                nlink: child.nlink,
+                files: child.files,
+                dirs: child.dirs,
+                stat: child.stat,
+                mtimes: child.mtimes(),
+                owner: None,
+                change: None,
+                marked: false,
+            });
+
+            // Recurse into directories
+            if !child.is_file && !child.children.is_empty() {
+                self.add_children(child_id, depth + 1, current_angle, child_end, child_size);
+            }
+
+            current_angle = child_end;
         }
-
-        current_angle = child_end;
     }
 }
 
@@ -162,6 +251,8 @@ pub fn draw_sunburst(
     width: f64,
     height: f64,
     hover_path: Option<&PathBuf>,
+    color_mode: ColorMode,
+    size_mode: SizeMode,
 ) {
     let center_x = width / 2.0;
     let center_y = height / 2.0;
@@ -172,53 +263,167 @@ pub fn draw_sunburst(
     cr.set_source_rgb(0.1, 0.07, 0.08);
     cr.paint().unwrap();
 
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+
     // Draw segments by depth (inner to outer)
     for depth in 0..=MAX_DEPTH {
         let inner_radius = if depth == 0 { 0.0 } else { ring_width * depth as f64 };
         let outer_radius = ring_width * (depth as f64 + 1.0);
 
         for segment in segments.iter().filter(|s| s.depth == depth) {
+            let is_hovered = hover_path == Some(&segment.path);
             draw_segment(
                 cr,
                 segment,
+                segment_color(segment, color_mode, now, is_hovered),
                 center_x,
                 center_y,
                 inner_radius,
                 outer_radius,
-                hover_path,
             );
         }
     }
 
     // Draw center text
     if let Some(root) = segments.first() {
-        draw_center_text(cr, root, center_x, center_y, ring_width);
+        draw_center_text(cr, root, size_mode, center_x, center_y, ring_width);
+    }
+
+    if matches!(color_mode, ColorMode::Newest | ColorMode::Oldest) && !segments.is_empty() {
+        let undated = segments.iter().any(|s| s.mtimes.is_none());
+        draw_age_legend(cr, now, undated, height);
     }
 }
 
-fn draw_segment(
-    cr: &Context,
+/// Fill colour for a segment under the given colour mode
+fn segment_color(
     segment: &Segment,
-    center_x: f64,
-    center_y: f64,
-    inner_radius: f64,
-    outer_radius: f64,
-    hover_path: Option<&PathBuf>,
-) {
-    let is_hovered = hover_path.map_or(false, |p| p == &segment.path);
-    let (r, g, b, a) = segment.file_type.color();
+    color_mode: ColorMode,
+    now: i64,
+    is_hovered: bool,
+) -> (f64, f64, f64, f64) {
+    let (r, g, b, a) = match (color_mode, segment.change) {
+        (ColorMode::Growth, Some((change, delta))) => growth_color(change, delta, segment.size),
+        (ColorMode::Newest | ColorMode::Oldest, _) => {
+            let mtime = segment.mtimes.map(|(oldest, newest)| match color_mode {
+                ColorMode::Oldest => oldest,
+                _ => newest,
+            });
+            let (r, g, b) = mtime.map_or(UNDATED_COLOR, |mtime| AGE_COLORS[age_bucket(mtime, now)]);
+            (r, g, b, 1.0)
+        }
+        (ColorMode::Owner, _) => {
+            let (r, g, b) = segment.owner.map_or(OTHER_OWNER_COLOR, |i| OWNER_COLORS[i]);
+            (r, g, b, 1.0)
+        }
+        _ => segment.file_type.color(),
+    };
 
     // Adjust color based on depth for visual hierarchy
     let depth_factor = 1.0 - (segment.depth as f64 * 0.1);
     let (r, g, b) = (r * depth_factor, g * depth_factor, b * depth_factor);
 
     // Brighten on hover
-    let (r, g, b) = if is_hovered {
-        ((r + 0.2).min(1.0), (g + 0.2).min(1.0), (b + 0.2).min(1.0))
+    if is_hovered {
+        (
+            (r + 0.2).min(1.0),
+            (g + 0.2).min(1.0),
+            (b + 0.2).min(1.0),
+            a,
+        )
     } else {
-        (r, g, b)
-    };
+        (r, g, b, a)
+    }
+}
 
+/// Hotter the more of an entry is new since the baseline
+fn growth_color(change: Change, delta: i64, size: u64) -> (f64, f64, f64, f64) {
+    match change {
+        // Brand new: white-hot
+        Change::Added => (1.0, 0.95, 0.45, 1.0),
+        // Grown: from ember to flame by the share of the entry that is new
+        Change::Grown => {
+            let t = (delta as f64 / size.max(1) as f64).clamp(0.0, 1.0).sqrt();
+            (0.55 + 0.45 * t, 0.25 - 0.1 * t, 0.1, 1.0)
+        }
+        // Shrunk: cooled off
+        Change::Shrunk => (0.3, 0.5, 0.85, 1.0),
+        Change::Unchanged | Change::Removed => (0.35, 0.32, 0.32, 1.0),
+    }
+}
+
+/// Index into `AGE_COLORS` for something last modified at `mtime`
+fn age_bucket(mtime: i64, now: i64) -> usize {
+    let age = now.saturating_sub(mtime);
+    AGE_BUCKETS
+        .iter()
+        .position(|&(limit, _)| age < limit)
+        .unwrap_or(AGE_BUCKETS.len())
+}
+
+/// List the age buckets and the dates they start from in the bottom-left corner
+fn draw_age_legend(cr: &Context, now: i64, undated: bool, height: f64) {
+    let cutoff = |age: i64| format_date(now.saturating_sub(age).max(0) as u64);
+    let mut rows: Vec<((f64, f64, f64), String)> = AGE_BUCKETS
+        .iter()
+        .zip(AGE_COLORS)
+        .map(|(&(limit, label), color)| (color, format!("{} (since {})", label, cutoff(limit))))
+        .collect();
+    let (oldest_limit, _) = AGE_BUCKETS[AGE_BUCKETS.len() - 1];
+    rows.push((
+        AGE_COLORS[AGE_BUCKETS.len()],
+        format!("Before {}", cutoff(oldest_limit)),
+    ));
+    if undated {
+        rows.push((UNDATED_COLOR, "No dated files".to_string()));
+    }
+    draw_legend(cr, &rows, height);
+}
+
+/// Show which colour belongs to which owner, in the bottom-left corner
+///
+/// `owners` is in the same order as `OWNER_COLORS`; `others` labels everyone else.
+pub fn draw_owner_legend(cr: &Context, owners: &[String], others: Option<&str>, height: f64) {
+    let mut rows: Vec<((f64, f64, f64), String)> = OWNER_COLORS
+        .into_iter()
+        .zip(owners.iter().cloned())
+        .collect();
+    if let Some(others) = others {
+        rows.push((OTHER_OWNER_COLOR, others.to_string()));
+    }
+    draw_legend(cr, &rows, height);
+}
+
+/// A column of colour swatches and their labels in the bottom-left corner
+fn draw_legend(cr: &Context, rows: &[((f64, f64, f64), String)], height: f64) {
+    const ROW: f64 = 18.0;
+    let left = 16.0;
+    let top = height - 16.0 - ROW * rows.len() as f64;
+
+    cr.select_font_face("Sans", FontSlant::Normal, FontWeight::Normal);
+    cr.set_font_size(12.0);
+    for (i, ((r, g, b), label)) in rows.iter().enumerate() {
+        let y = top + ROW * i as f64;
+        cr.set_source_rgb(*r, *g, *b);
+        cr.rectangle(left, y, 12.0, 12.0);
+        cr.fill().unwrap();
+        cr.set_source_rgb(1.0, 0.87, 0.8);
+        cr.move_to(left + 20.0, y + 10.5);
+        cr.show_text(label).unwrap();
+    }
+}
+
+fn draw_segment(
+    cr: &Context,
+    segment: &Segment,
+    (r, g, b, a): (f64, f64, f64, f64),
+    center_x: f64,
+    center_y: f64,
+    inner_radius: f64,
+    outer_radius: f64,
+) {
     cr.set_source_rgba(r, g, b, a);
 
     if segment.depth == 0 {
@@ -281,12 +486,55 @@ fn draw_segment(
         );
         cr.close_path();
         cr.stroke().unwrap();
+
+        // Marked entries look charred, with a bright rim
+        if segment.marked {
+            cr.new_path();
+            cr.arc(
+                center_x,
+                center_y,
+                outer_radius,
+                segment.start_angle,
+                segment.end_angle,
+            );
+            cr.arc_negative(
+                center_x,
+                center_y,
+                inner_radius,
+                segment.end_angle,
+                segment.start_angle,
+            );
+            cr.close_path();
+            cr.set_source_rgba(0.05, 0.03, 0.03, 0.6);
+            cr.fill_preserve().unwrap();
+            cr.set_source_rgba(1.0, 0.95, 0.8, 0.9);
+            cr.set_line_width(2.0);
+            cr.stroke().unwrap();
+        }
+
+        // Dashed outline marks files whose inode is shared with other paths
+        if segment.is_file && segment.nlink > 1 {
+            cr.set_source_rgba(1.0, 0.85, 0.4, 0.9);
+            cr.set_line_width(1.0);
+            cr.set_dash(&[3.0, 3.0], 0.0);
+            cr.new_path();
+            cr.arc(
+                center_x,
+                center_y,
+                outer_radius - 2.0,
+                segment.start_angle,
+                segment.end_angle,
+            );
+            cr.stroke().unwrap();
+            cr.set_dash(&[], 0.0);
+        }
     }
 }
 
 fn draw_center_text(
     cr: &Context,
     root: &Segment,
+    size_mode: SizeMode,
     center_x: f64,
     center_y: f64,
     _ring_width: f64,
@@ -309,7 +557,7 @@ fn draw_center_text(
 
     // Draw size in bright flame color
     cr.set_source_rgb(1.0, 0.6, 0.2);
-    let size_text = format_size(root.size);
+    let size_text = size_mode.format(root.size);
     cr.set_font_size(14.0);
     let extents = cr.text_extents(&size_text).unwrap();
     cr.move_to(center_x - extents.width() / 2.0, center_y + 12.0);
@@ -0,0 +1,300 @@
+use std::env;
+use std::fs::{self, DirBuilder, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Sticky bit, required on a shared `$topdir/.Trash`
+const S_ISVTX: u32 = 0o1000;
+
+/// An item moved into the trash, with enough to put it back
+#[derive(Debug, Clone)]
+pub struct TrashItem {
+    /// Where the item lived before it was trashed
+    pub original_path: PathBuf,
+    /// Where it lives now, inside the trash's `files` directory
+    pub trashed_path: PathBuf,
+    /// Its `.trashinfo` record
+    pub info_path: PathBuf,
+}
+
+/// A trash directory with its `files` and `info` subdirectories
+struct TrashDir {
+    /// Root of the trash (`~/.local/share/Trash`, `$topdir/.Trash-$uid`, ...)
+    root: PathBuf,
+    /// Mount point the trash lives on, for paths relative to it;
+    /// `None` for the home trash, which records absolute paths
+    topdir: Option<PathBuf>,
+}
+
+/// Move `path` to the trash following the freedesktop.org Trash spec
+pub fn trash(path: &Path) -> io::Result<TrashItem> {
+    trash_with_data_home(path, data_home().as_deref())
+}
+
+/// Like `trash`, with the home trash under `data_home` if there is one
+fn trash_with_data_home(path: &Path, data_home: Option<&Path>) -> io::Result<TrashItem> {
+    let path = std::path::absolute(path)?;
+    let metadata = fs::symlink_metadata(&path)?;
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash a root"))?;
+
+    let trash_dir = find_trash_dir(&path, metadata.dev(), data_home)?;
+    let files = trash_dir.root.join("files");
+    let info = trash_dir.root.join("info");
+    create_private_dir(&files)?;
+    create_private_dir(&info)?;
+
+    // Claim a name by creating its .trashinfo exclusively, then move the item
+    let (trashed_path, info_path, mut info_file) = claim_name(&files, &info, name)?;
+    let original = match &trash_dir.topdir {
+        Some(topdir) => path.strip_prefix(topdir).unwrap_or(&path),
+        None => &path,
+    };
+    let written = write!(
+        info_file,
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        encode_path(original),
+        local_timestamp()
+    )
+    .and_then(|_| info_file.sync_all());
+
+    if let Err(e) = written.and_then(|_| fs::rename(&path, &trashed_path)) {
+        let _ = fs::remove_file(&info_path);
+        return Err(e);
+    }
+    Ok(TrashItem {
+        original_path: path,
+        trashed_path,
+        info_path,
+    })
+}
+
+/// Move a trashed item back to where it came from
+///
+/// Refuses to overwrite anything that has since appeared at the original path.
+pub fn restore(item: &TrashItem) -> io::Result<()> {
+    if fs::symlink_metadata(&item.original_path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", item.original_path.display()),
+        ));
+    }
+    if let Some(parent) = item.original_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(&item.trashed_path, &item.original_path)?;
+    // The item is back; a stale .trashinfo only confuses file managers
+    let _ = fs::remove_file(&item.info_path);
+    Ok(())
+}
+
+/// Pick the trash for an item on device `dev`
+///
+/// The home trash in `data_home` is used when it's on the same filesystem;
+/// otherwise the item goes to `$topdir/.Trash/$uid` if the admin set up a
+/// shared trash, or `$topdir/.Trash-$uid` on the item's own filesystem.
+fn find_trash_dir(path: &Path, dev: u64, data_home: Option<&Path>) -> io::Result<TrashDir> {
+    if let Some(data_home) = data_home.filter(|d| fs::create_dir_all(d).is_ok()) {
+        if fs::metadata(data_home).is_ok_and(|m| m.dev() == dev) {
+            return Ok(TrashDir {
+                root: data_home.join("Trash"),
+                topdir: None,
+            });
+        }
+    }
+
+    let topdir = mount_point(path, dev);
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+
+    // A shared .Trash only counts if it's a real, sticky directory
+    let shared = topdir.join(".Trash");
+    let shared_ok = fs::symlink_metadata(&shared)
+        .is_ok_and(|m| m.is_dir() && m.permissions().mode() & S_ISVTX != 0);
+    if shared_ok {
+        let root = shared.join(uid.to_string());
+        if create_private_dir(&root).is_ok() {
+            return Ok(TrashDir {
+                root,
+                topdir: Some(topdir),
+            });
+        }
+    }
+
+    let root = topdir.join(format!(".Trash-{}", uid));
+    create_private_dir(&root)?;
+    Ok(TrashDir {
+        root,
+        topdir: Some(topdir),
+    })
+}
+
+/// `$XDG_DATA_HOME`, falling back to `~/.local/share`
+fn data_home() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+}
+
+/// Walk up from `path` to the top of the filesystem on device `dev`
+fn mount_point(path: &Path, dev: u64) -> PathBuf {
+    let mut top = path.parent().unwrap_or(path);
+    while let Some(parent) = top.parent() {
+        if !fs::metadata(parent).is_ok_and(|m| m.dev() == dev) {
+            break;
+        }
+        top = parent;
+    }
+    top.to_path_buf()
+}
+
+fn create_private_dir(path: &Path) -> io::Result<()> {
+    DirBuilder::new().recursive(true).mode(0o700).create(path)
+}
+
+/// Find a free name in the trash, returning the files/ path, the info path
+/// and the freshly created (exclusive) .trashinfo file
+fn claim_name(
+    files: &Path,
+    info: &Path,
+    name: &std::ffi::OsStr,
+) -> io::Result<(PathBuf, PathBuf, File)> {
+    for n in 1u32.. {
+        let mut candidate = name.to_os_string();
+        if n > 1 {
+            candidate.push(format!(".{}", n));
+        }
+        let trashed_path = files.join(&candidate);
+        candidate.push(".trashinfo");
+        let info_path = info.join(&candidate);
+
+        if fs::symlink_metadata(&trashed_path).is_ok() {
+            continue;
+        }
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(file) => return Ok((trashed_path, info_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("ran out of trash names")
+}
+
+/// Percent-encode a path for the Path= key, leaving `/` and unreserved bytes
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Current local time as `YYYY-MM-DDThh:mm:ss`, as the spec asks for
+fn local_timestamp() -> String {
+    // SAFETY: time accepts a null pointer, and localtime_r only writes to the
+    // tm we own; a zeroed tm is a valid value to pass in
+    let tm = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        libc::localtime_r(&now, &mut tm);
+        tm
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn encodes_paths_for_trashinfo() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"/home/me/notes.txt", "/home/me/notes.txt"),
+            (b"/a b/100%", "/a%20b/100%25"),
+            (b"/x/[draft] #1?", "/x/%5Bdraft%5D%20%231%3F"),
+            (b"relative/keep-_.~", "relative/keep-_.~"),
+            (b"/caf\xc3\xa9", "/caf%C3%A9"),
+            (b"/latin1-\xe9", "/latin1-%E9"),
+        ];
+        for (path, encoded) in cases {
+            let path = Path::new(OsStr::from_bytes(path));
+            assert_eq!(encode_path(path), *encoded, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn claims_numbered_names_on_collision() {
+        let temp = tempfile::tempdir().unwrap();
+        let files = temp.path().join("files");
+        let info = temp.path().join("info");
+        create_private_dir(&files).unwrap();
+        create_private_dir(&info).unwrap();
+        let name = OsStr::new("report.pdf");
+
+        let (trashed, info_path, _) = claim_name(&files, &info, name).unwrap();
+        assert_eq!(trashed, files.join("report.pdf"));
+        assert_eq!(info_path, info.join("report.pdf.trashinfo"));
+
+        // The .trashinfo from the first claim is enough to take the name
+        let (trashed, info_path, _) = claim_name(&files, &info, name).unwrap();
+        assert_eq!(trashed, files.join("report.pdf.2"));
+        assert_eq!(info_path, info.join("report.pdf.2.trashinfo"));
+
+        // So is a file left in files/ without its record
+        fs::write(files.join("report.pdf.3"), "").unwrap();
+        let (trashed, info_path, _) = claim_name(&files, &info, name).unwrap();
+        assert_eq!(trashed, files.join("report.pdf.4"));
+        assert_eq!(info_path, info.join("report.pdf.4.trashinfo"));
+    }
+
+    #[test]
+    fn trashes_and_restores_through_the_home_trash() {
+        let temp = tempfile::tempdir().unwrap();
+        let data_home = temp.path().join("data");
+        let original = temp.path().join("my file.txt");
+        fs::write(&original, "contents").unwrap();
+
+        let item = trash_with_data_home(&original, Some(&data_home)).unwrap();
+        assert!(!original.exists());
+        assert_eq!(item.trashed_path, data_home.join("Trash/files/my file.txt"));
+        assert_eq!(
+            item.info_path,
+            data_home.join("Trash/info/my file.txt.trashinfo")
+        );
+        let info = fs::read_to_string(&item.info_path).unwrap();
+        let mut lines = info.lines();
+        assert_eq!(lines.next(), Some("[Trash Info]"));
+        assert_eq!(
+            lines.next(),
+            Some(format!("Path={}", encode_path(&original)).as_str())
+        );
+        let date = lines.next().unwrap().strip_prefix("DeletionDate=").unwrap();
+        assert_eq!(date.len(), "2024-01-31T12:34:56".len());
+        assert_eq!(&date[10..11], "T");
+
+        restore(&item).unwrap();
+        assert_eq!(fs::read_to_string(&original).unwrap(), "contents");
+        assert!(!item.info_path.exists());
+    }
+}
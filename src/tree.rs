@@ -0,0 +1,738 @@
+//! The scanned tree as a compact arena of nodes
+//!
+//! Scans and snapshots build their trees here directly, where finding an
+//! entry, its parent or its children never means walking the whole tree.
+//! `DirEntry` values are only used to hand single entries, or small
+//! subtrees such as an undo, in and out.
+//!
+//! Nodes keep only their own file name, interned so that the thousands of
+//! `index.js` and `package.json` files share one copy, plus a link to their
+//! parent. Full paths are rebuilt when asked for.
+
+use crate::model::{DirEntry, FileType, SizeMode, Stat};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// Handle to a node of a `ScanTree`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+/// Handle to an interned file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct NameId(u32);
+
+/// A file or directory in a `ScanTree`
+#[derive(Debug, Clone)]
+pub struct Node {
+    name: NameId,
+    /// Apparent size in bytes, including everything beneath a directory
+    pub size: u64,
+    /// Allocated size in bytes, including everything beneath a directory
+    pub disk_size: u64,
+    /// Files in this subtree (1 for a file)
+    pub files: u64,
+    /// Directories in this subtree, itself included
+    pub dirs: u64,
+    /// Inodes used by this subtree; a hard link counted elsewhere adds none
+    pub inodes: u64,
+    pub file_type: FileType,
+    pub is_file: bool,
+    /// Number of hard links to this file's inode
+    pub nlink: u64,
+    /// Mount point of another filesystem that was not traversed
+    pub mount_point: bool,
+    pub stat: Stat,
+    /// Modification time of the newest and oldest file in this subtree,
+    /// `i64::MIN` and `i64::MAX` while there are none
+    newest: i64,
+    oldest: i64,
+    pub parent: Option<NodeId>,
+    /// Largest first
+    pub children: Vec<NodeId>,
+}
+
+impl Node {
+    /// Size measured the given way
+    pub fn size_in(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.size,
+            SizeMode::Allocated => self.disk_size,
+            SizeMode::Inodes => self.inodes,
+        }
+    }
+
+    /// Number of items (files + directories) in this subtree, itself included
+    pub fn item_count(&self) -> u64 {
+        self.files + self.dirs
+    }
+
+    /// Whether this file shares its inode with other paths
+    pub fn is_hard_linked(&self) -> bool {
+        self.is_file && self.nlink > 1
+    }
+
+    /// Modification times of the oldest and newest file in this subtree
+    pub fn mtimes(&self) -> Option<(i64, i64)> {
+        (self.newest != i64::MIN).then_some((self.oldest, self.newest))
+    }
+}
+
+/// What a subtree contributes to each of its ancestors
+#[derive(Debug, Clone, Copy)]
+struct Totals {
+    size: u64,
+    disk_size: u64,
+    files: u64,
+    dirs: u64,
+    inodes: u64,
+}
+
+impl Totals {
+    fn of(node: &Node) -> Self {
+        Self {
+            size: node.size,
+            disk_size: node.disk_size,
+            files: node.files,
+            dirs: node.dirs,
+            inodes: node.inodes,
+        }
+    }
+
+    /// Add these totals to `node`'s, or take them away
+    fn apply(self, node: &mut Node, add: bool) {
+        if add {
+            node.size += self.size;
+            node.disk_size += self.disk_size;
+            node.files += self.files;
+            node.dirs += self.dirs;
+            node.inodes += self.inodes;
+        } else {
+            node.size = node.size.saturating_sub(self.size);
+            node.disk_size = node.disk_size.saturating_sub(self.disk_size);
+            node.files = node.files.saturating_sub(self.files);
+            node.dirs = node.dirs.saturating_sub(self.dirs);
+            node.inodes = node.inodes.saturating_sub(self.inodes);
+        }
+    }
+}
+
+/// Every distinct file name in a tree, stored once
+#[derive(Debug, Clone, Default)]
+struct Names {
+    ids: HashMap<Arc<OsStr>, NameId>,
+    names: Vec<Arc<OsStr>>,
+}
+
+impl Names {
+    fn intern(&mut self, name: &OsStr) -> NameId {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = NameId(self.names.len() as u32);
+        let name: Arc<OsStr> = Arc::from(name);
+        self.names.push(name.clone());
+        self.ids.insert(name, id);
+        id
+    }
+
+    fn lookup(&self, name: &OsStr) -> Option<NameId> {
+        self.ids.get(name).copied()
+    }
+
+    fn get(&self, id: NameId) -> &OsStr {
+        &self.names[id.0 as usize]
+    }
+}
+
+/// Nodes in each chunk of `Nodes`
+const CHUNK: usize = 4096;
+
+/// Node storage that grows a chunk at a time, so a big tree never copies
+/// every node it holds to make room, or reserves far more than it uses
+#[derive(Debug, Clone, Default)]
+struct Nodes {
+    /// Every chunk but the last holds exactly `CHUNK` slots
+    chunks: Vec<Vec<Option<Node>>>,
+}
+
+impl Nodes {
+    fn slot(&self, id: NodeId) -> &Option<Node> {
+        let i = id.0 as usize;
+        &self.chunks[i / CHUNK][i % CHUNK]
+    }
+
+    fn slot_mut(&mut self, id: NodeId) -> &mut Option<Node> {
+        let i = id.0 as usize;
+        &mut self.chunks[i / CHUNK][i % CHUNK]
+    }
+
+    fn push(&mut self, node: Node) -> NodeId {
+        if self.chunks.last().is_none_or(|chunk| chunk.len() == CHUNK) {
+            self.chunks.push(Vec::new());
+        }
+        let full = (self.chunks.len() - 1) * CHUNK;
+        let chunk = self.chunks.last_mut().expect("a chunk was just added");
+        chunk.push(Some(node));
+        NodeId((full + chunk.len() - 1) as u32)
+    }
+}
+
+/// A scanned tree that can be searched and edited in place
+#[derive(Debug, Clone)]
+pub struct ScanTree {
+    /// Removed nodes leave an empty slot for the next insert
+    nodes: Nodes,
+    free: Vec<NodeId>,
+    names: Names,
+    /// Each node by its parent and file name
+    index: HashMap<(NodeId, NameId), NodeId>,
+    root: NodeId,
+    root_path: PathBuf,
+}
+
+impl ScanTree {
+    /// Take over `entry` and everything beneath it as a new tree
+    pub fn from_entry(entry: DirEntry) -> Self {
+        let mut tree = Self {
+            nodes: Nodes::default(),
+            free: Vec::new(),
+            names: Names::default(),
+            index: HashMap::new(),
+            root: NodeId(0),
+            root_path: entry.path.clone(),
+        };
+        tree.root = tree.add(entry, None);
+        tree
+    }
+
+    pub fn root(&self) -> NodeId {
+        self.root
+    }
+
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+
+    /// The node for `id`, which must not have been removed
+    pub fn get(&self, id: NodeId) -> &Node {
+        self.nodes
+            .slot(id)
+            .as_ref()
+            .expect("node was removed from the tree")
+    }
+
+    fn get_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes
+            .slot_mut(id)
+            .as_mut()
+            .expect("node was removed from the tree")
+    }
+
+    /// Name to show for `id`; the root goes by its full path if it has no name
+    pub fn name(&self, id: NodeId) -> Cow<'_, str> {
+        if id == self.root {
+            let name = self.root_path.file_name();
+            return name.unwrap_or(self.root_path.as_os_str()).to_string_lossy();
+        }
+        self.file_name(id).to_string_lossy()
+    }
+
+    /// Name of `id` within its parent directory, exactly as on disk
+    pub fn file_name(&self, id: NodeId) -> &OsStr {
+        self.names.get(self.get(id).name)
+    }
+
+    /// Full path of `id`, rebuilt from the names of its ancestors
+    pub fn path(&self, id: NodeId) -> PathBuf {
+        let mut names = Vec::new();
+        let mut current = id;
+        while let Some(parent) = self.get(current).parent {
+            names.push(self.file_name(current));
+            current = parent;
+        }
+        let mut path = self.root_path.clone();
+        path.extend(names.iter().rev());
+        path
+    }
+
+    /// Look up an entry by its full path
+    pub fn find(&self, path: &Path) -> Option<NodeId> {
+        let rest = path.strip_prefix(&self.root_path).ok()?;
+        let mut current = self.root;
+        for component in rest.components() {
+            let Component::Normal(name) = component else {
+                return None;
+            };
+            current = self.child(current, name)?;
+        }
+        Some(current)
+    }
+
+    /// The child of `parent` called `name`
+    pub fn child(&self, parent: NodeId, name: &OsStr) -> Option<NodeId> {
+        let name = self.names.lookup(name)?;
+        self.index.get(&(parent, name)).copied()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.find(path).is_some()
+    }
+
+    /// Put `entry` under its parent directory, replacing anything already at
+    /// its path and keeping ancestor totals in step
+    ///
+    /// Returns false if the parent isn't part of the tree.
+    pub fn insert(&mut self, entry: DirEntry, mode: SizeMode) -> bool {
+        let Some(parent) = entry.path.parent().and_then(|p| self.find(p)) else {
+            return false;
+        };
+        if self.get(parent).is_file {
+            return false;
+        }
+        self.remove(&entry.path, mode);
+
+        let id = self.add(entry, Some(parent));
+        self.attach(parent, id, mode);
+        true
+    }
+
+    /// Like `insert`, for a subtree scanned into a tree of its own
+    pub fn insert_tree(&mut self, subtree: ScanTree, mode: SizeMode) -> bool {
+        let path = subtree.root_path();
+        let Some(parent) = path.parent().and_then(|p| self.find(p)) else {
+            return false;
+        };
+        if self.get(parent).is_file {
+            return false;
+        }
+        self.remove(path, mode);
+
+        let id = self.copy(&subtree, subtree.root(), parent);
+        self.attach(parent, id, mode);
+        true
+    }
+
+    /// Hang the new subtree `id` under `parent` and add it to the totals above
+    fn attach(&mut self, parent: NodeId, id: NodeId, mode: SizeMode) {
+        self.sort_subtree(id, mode);
+        self.get_mut(parent).children.push(id);
+        self.adjust_ancestors(id, Totals::of(self.get(id)), true, mode);
+    }
+
+    /// Fold the rest of a live scan into the subtrees it streamed earlier
+    ///
+    /// `rest` must have the same root and hold none of what this tree does;
+    /// its root's stat replaces this one's.
+    pub fn absorb(&mut self, rest: ScanTree, mode: SizeMode) {
+        let root = self.root;
+        for &child in &rest.get(rest.root).children {
+            let id = self.copy(&rest, child, root);
+            self.get_mut(root).children.push(id);
+        }
+        self.get_mut(root).stat = rest.get(rest.root).stat;
+        self.finish_dir(root, mode);
+    }
+
+    /// Count file `id` as a hard link whose inode is counted under another
+    /// path, taking its sizes off every ancestor
+    pub fn count_elsewhere(&mut self, id: NodeId, mode: SizeMode) {
+        let node = self.get(id);
+        let totals = Totals {
+            size: node.size,
+            disk_size: node.disk_size,
+            files: 0,
+            dirs: 0,
+            inodes: node.inodes,
+        };
+        self.adjust_ancestors(id, totals, false, mode);
+        let node = self.get_mut(id);
+        (node.size, node.disk_size, node.inodes) = (0, 0, 0);
+    }
+
+    /// Add `entry` under directory `parent` while building a tree, leaving
+    /// the parent's totals to `finish_dir`
+    pub fn add_child(&mut self, parent: NodeId, entry: DirEntry) -> NodeId {
+        let id = self.add(entry, Some(parent));
+        self.get_mut(parent).children.push(id);
+        id
+    }
+
+    /// Copy `subtree` in under directory `parent` while building a tree,
+    /// leaving the parent's totals to `finish_dir`
+    pub fn graft(&mut self, parent: NodeId, subtree: ScanTree) -> NodeId {
+        let id = self.copy(&subtree, subtree.root(), parent);
+        self.get_mut(parent).children.push(id);
+        id
+    }
+
+    /// Total up directory `id` from its children, which must be finished
+    /// already, and sort them by size
+    pub fn finish_dir(&mut self, id: NodeId, mode: SizeMode) {
+        let children = std::mem::take(&mut self.get_mut(id).children);
+        let node = self.get_mut(id);
+        (
+            node.size,
+            node.disk_size,
+            node.files,
+            node.dirs,
+            node.inodes,
+        ) = (0, 0, 0, 1, 1);
+        for &child in &children {
+            let totals = Totals::of(self.get(child));
+            totals.apply(self.get_mut(id), true);
+        }
+        self.get_mut(id).children = children;
+        self.update_mtimes(id);
+        self.sort_children(id, mode);
+    }
+
+    /// Detach the entry at `path`, keeping ancestor totals in step, and return
+    /// it as a `DirEntry`
+    ///
+    /// The root can't be removed.
+    pub fn remove(&mut self, path: &Path, mode: SizeMode) -> Option<DirEntry> {
+        let id = self.find(path)?;
+        let parent = self.get(id).parent?;
+        self.get_mut(parent).children.retain(|&c| c != id);
+        self.adjust_ancestors(id, Totals::of(self.get(id)), false, mode);
+        Some(self.take(id, self.path(id)))
+    }
+
+    /// Mount points anywhere in the tree that the scan didn't cross
+    pub fn skipped_mount_points(&self) -> Vec<PathBuf> {
+        let mut mounts = Vec::new();
+        let mut stack = vec![self.root];
+        while let Some(id) = stack.pop() {
+            let node = self.get(id);
+            if node.mount_point {
+                mounts.push(self.path(id));
+            }
+            stack.extend(&node.children);
+        }
+        mounts
+    }
+
+    /// Sort every directory's children by size, largest first
+    pub fn sort_by_size(&mut self, mode: SizeMode) {
+        self.sort_subtree(self.root, mode);
+    }
+
+    fn sort_subtree(&mut self, id: NodeId, mode: SizeMode) {
+        self.sort_children(id, mode);
+        for child in self.get(id).children.clone() {
+            self.sort_subtree(child, mode);
+        }
+    }
+
+    fn sort_children(&mut self, id: NodeId, mode: SizeMode) {
+        let mut children = std::mem::take(&mut self.get_mut(id).children);
+        children.sort_by_key(|&c| Reverse(self.get(c).size_in(mode)));
+        self.get_mut(id).children = children;
+    }
+
+    /// Add (or take away) the totals of `id` at every ancestor, re-sorting each
+    /// level and recomputing its mtime range
+    fn adjust_ancestors(&mut self, id: NodeId, totals: Totals, add: bool, mode: SizeMode) {
+        let mut current = self.get(id).parent;
+        while let Some(ancestor) = current {
+            let node = self.get_mut(ancestor);
+            totals.apply(node, add);
+            current = node.parent;
+            // The entry that changed may have moved in the ranking
+            self.sort_children(ancestor, mode);
+            self.update_mtimes(ancestor);
+        }
+    }
+
+    /// Recompute the mtime range of directory `id` from its children
+    ///
+    /// Unlike the totals, a range can't be adjusted by what was taken away.
+    fn update_mtimes(&mut self, id: NodeId) {
+        let (mut newest, mut oldest) = (i64::MIN, i64::MAX);
+        for &child in &self.get(id).children {
+            let child = self.get(child);
+            newest = newest.max(child.newest);
+            oldest = oldest.min(child.oldest);
+        }
+        let node = self.get_mut(id);
+        (node.newest, node.oldest) = (newest, oldest);
+    }
+
+    /// Store `entry` and everything beneath it, returning its id
+    fn add(&mut self, entry: DirEntry, parent: Option<NodeId>) -> NodeId {
+        let DirEntry {
+            path,
+            size,
+            disk_size,
+            files,
+            dirs,
+            inodes,
+            file_type,
+            children,
+            is_file,
+            nlink,
+            mount_point,
+            stat,
+            ..
+        } = entry;
+
+        let name = self.names.intern(path.file_name().unwrap_or_default());
+        // Directories take theirs from the files beneath, below
+        let (newest, oldest) = if is_file && stat.is_recorded() {
+            (stat.mtime, stat.mtime)
+        } else {
+            (i64::MIN, i64::MAX)
+        };
+        let id = self.alloc(Node {
+            name,
+            size,
+            disk_size,
+            files,
+            dirs,
+            inodes,
+            file_type,
+            is_file,
+            nlink,
+            mount_point,
+            stat,
+            newest,
+            oldest,
+            parent,
+            children: Vec::with_capacity(children.len()),
+        });
+        if let Some(parent) = parent {
+            self.index.insert((parent, name), id);
+        }
+
+        if !is_file {
+            // Recounted from the children, whatever the entry claimed
+            let node = self.get_mut(id);
+            (
+                node.size,
+                node.disk_size,
+                node.files,
+                node.dirs,
+                node.inodes,
+            ) = (0, 0, 0, 1, 1);
+            for child in children {
+                let child = self.add(child, Some(id));
+                let totals = Totals::of(self.get(child));
+                let (newest, oldest) = (self.get(child).newest, self.get(child).oldest);
+                let node = self.get_mut(id);
+                totals.apply(node, true);
+                node.newest = node.newest.max(newest);
+                node.oldest = node.oldest.min(oldest);
+                node.children.push(child);
+            }
+        }
+        id
+    }
+
+    /// Copy node `id` of `from`, and everything beneath it, in under `parent`
+    fn copy(&mut self, from: &ScanTree, id: NodeId, parent: NodeId) -> NodeId {
+        let node = from.get(id);
+        let name = self.names.intern(from.file_name(id));
+        let copy = self.alloc(Node {
+            name,
+            parent: Some(parent),
+            children: Vec::with_capacity(node.children.len()),
+            ..*node
+        });
+        self.index.insert((parent, name), copy);
+        for &child in &node.children {
+            let child = self.copy(from, child, copy);
+            self.get_mut(copy).children.push(child);
+        }
+        copy
+    }
+
+    fn alloc(&mut self, node: Node) -> NodeId {
+        match self.free.pop() {
+            Some(id) => {
+                *self.nodes.slot_mut(id) = Some(node);
+                id
+            }
+            None => self.nodes.push(node),
+        }
+    }
+
+    /// Free `id` and everything beneath it, returning them as a `DirEntry`
+    fn take(&mut self, id: NodeId, path: PathBuf) -> DirEntry {
+        let node = self
+            .nodes
+            .slot_mut(id)
+            .take()
+            .expect("node was removed from the tree");
+        self.free.push(id);
+        if let Some(parent) = node.parent {
+            self.index.remove(&(parent, node.name));
+        }
+        let children = node
+            .children
+            .iter()
+            .map(|&c| {
+                let child_path = path.join(self.file_name(c));
+                self.take(c, child_path)
+            })
+            .collect();
+        DirEntry {
+            path,
+            size: node.size,
+            disk_size: node.disk_size,
+            files: node.files,
+            dirs: node.dirs,
+            inodes: node.inodes,
+            file_type: node.file_type,
+            children,
+            is_file: node.is_file,
+            nlink: node.nlink,
+            mount_point: node.mount_point,
+            stat: node.stat,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64) -> DirEntry {
+        DirEntry::new_file(PathBuf::from(path), size, size.div_ceil(4096) * 4096)
+    }
+
+    fn dir(path: &str) -> DirEntry {
+        DirEntry::new_dir(PathBuf::from(path))
+    }
+
+    /// /r holding a.txt (100 bytes) and src/ with b.rs (300) and c.rs (50),
+    /// built the way a scan builds it
+    fn sample() -> ScanTree {
+        let mut tree = ScanTree::from_entry(dir("/r"));
+        let root = tree.root();
+        tree.add_child(root, file("/r/a.txt", 100));
+        let src = tree.add_child(root, dir("/r/src"));
+        tree.add_child(src, file("/r/src/c.rs", 50));
+        tree.add_child(src, file("/r/src/b.rs", 300));
+        tree.finish_dir(src, SizeMode::Apparent);
+        tree.finish_dir(root, SizeMode::Apparent);
+        tree
+    }
+
+    fn totals(tree: &ScanTree, path: &str) -> (u64, u64, u64, u64) {
+        let node = tree.get(tree.find(Path::new(path)).unwrap());
+        (node.size, node.files, node.dirs, node.inodes)
+    }
+
+    fn names(tree: &ScanTree, path: &str) -> Vec<String> {
+        let id = tree.find(Path::new(path)).unwrap();
+        let children = &tree.get(id).children;
+        children
+            .iter()
+            .map(|&c| tree.name(c).into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn finished_directories_total_and_sort_their_children() {
+        let tree = sample();
+        assert_eq!(totals(&tree, "/r"), (450, 3, 2, 5));
+        assert_eq!(totals(&tree, "/r/src"), (350, 2, 1, 3));
+        assert_eq!(names(&tree, "/r"), ["src", "a.txt"]);
+        assert_eq!(names(&tree, "/r/src"), ["b.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn entries_are_found_by_path_and_name() {
+        let tree = sample();
+        let src = tree.find(Path::new("/r/src")).unwrap();
+        let b = tree.child(src, OsStr::new("b.rs")).unwrap();
+        assert_eq!(tree.find(Path::new("/r/src/b.rs")), Some(b));
+        assert_eq!(tree.path(b), Path::new("/r/src/b.rs"));
+        assert_eq!(tree.get(b).parent, Some(src));
+        assert_eq!(tree.find(Path::new("/r")), Some(tree.root()));
+
+        assert!(!tree.contains(Path::new("/r/src/missing.rs")));
+        assert!(!tree.contains(Path::new("/r/a.txt/inside")));
+        assert!(!tree.contains(Path::new("/r/src/../a.txt")));
+        assert!(!tree.contains(Path::new("/elsewhere")));
+    }
+
+    #[test]
+    fn inserts_and_removals_keep_ancestor_totals() {
+        let mut tree = sample();
+        assert!(tree.insert(file("/r/src/b.rs", 1000), SizeMode::Apparent));
+        assert_eq!(totals(&tree, "/r"), (1150, 3, 2, 5));
+        assert_eq!(names(&tree, "/r/src"), ["b.rs", "c.rs"]);
+
+        let removed = tree
+            .remove(Path::new("/r/src"), SizeMode::Apparent)
+            .unwrap();
+        assert_eq!((removed.size, removed.children.len()), (1050, 2));
+        assert_eq!(totals(&tree, "/r"), (100, 1, 1, 2));
+        assert!(!tree.contains(Path::new("/r/src/c.rs")));
+
+        // Parents must already be in the tree, and be directories
+        assert!(!tree.insert(file("/r/src/d.rs", 1), SizeMode::Apparent));
+        assert!(!tree.insert(file("/r/a.txt/d.rs", 1), SizeMode::Apparent));
+        assert!(tree.remove(Path::new("/r"), SizeMode::Apparent).is_none());
+    }
+
+    #[test]
+    fn subtrees_scanned_apart_join_the_tree() {
+        let mut tree = sample();
+        let mut subtree = ScanTree::from_entry(dir("/r/src"));
+        let root = subtree.root();
+        subtree.add_child(root, file("/r/src/new.rs", 2000));
+        subtree.finish_dir(root, SizeMode::Apparent);
+
+        // The rescanned directory replaces the old one outright
+        assert!(tree.insert_tree(subtree, SizeMode::Apparent));
+        assert_eq!(totals(&tree, "/r"), (2100, 2, 2, 4));
+        assert_eq!(names(&tree, "/r/src"), ["new.rs"]);
+        assert!(!tree.contains(Path::new("/r/src/b.rs")));
+
+        // What a live scan sends last joins what it streamed
+        let mut rest = ScanTree::from_entry(dir("/r"));
+        let root = rest.root();
+        rest.add_child(root, file("/r/z.log", 5000));
+        rest.finish_dir(root, SizeMode::Apparent);
+        tree.absorb(rest, SizeMode::Apparent);
+        assert_eq!(totals(&tree, "/r"), (7100, 3, 2, 5));
+        assert_eq!(names(&tree, "/r"), ["z.log", "src", "a.txt"]);
+    }
+
+    #[test]
+    fn hard_links_counted_elsewhere_add_no_size() {
+        let mut tree = ScanTree::from_entry(dir("/r"));
+        let root = tree.root();
+        let a = tree.add_child(root, dir("/r/a"));
+        let b = tree.add_child(root, dir("/r/b"));
+        let mut first = file("/r/a/link", 100);
+        first.nlink = 2;
+        let mut second = DirEntry {
+            inodes: 0,
+            size: 0,
+            disk_size: 0,
+            ..first.clone()
+        };
+        second.path = PathBuf::from("/r/b/link");
+        tree.add_child(a, first);
+        tree.add_child(b, second);
+        for id in [a, b, root] {
+            tree.finish_dir(id, SizeMode::Apparent);
+        }
+        // Both names count as files, but the inode and bytes only once
+        assert_eq!(totals(&tree, "/r"), (100, 2, 3, 4));
+        assert_eq!(totals(&tree, "/r/b"), (0, 1, 1, 1));
+
+        let link = tree.find(Path::new("/r/a/link")).unwrap();
+        assert!(tree.get(link).is_hard_linked());
+        tree.count_elsewhere(link, SizeMode::Apparent);
+        assert_eq!(totals(&tree, "/r"), (0, 2, 3, 3));
+        assert_eq!(totals(&tree, "/r/a"), (0, 1, 1, 1));
+    }
+}
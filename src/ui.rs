@@ -1,20 +1,35 @@
-use crate::actions::{delete_entry, DeleteResult};
+use crate::actions::{delete_as_admin, delete_entry, helper_available, DeleteMode, DeleteResult};
 use crate::app::AppState;
-use crate::model::{format_size, is_protected_path};
-use crate::scanner::{scan_directory, ScanProgress};
-use crate::sunburst::{draw_sunburst, find_segment_at_point, get_ring_width};
+use crate::diff::{format_delta, Change, DeltaEntry};
+use crate::model::{format_mode, format_size, format_timestamp, is_protected_path, SizeMode};
+use crate::owners::{owner_usage, OwnerNames, Usage};
+use crate::plan::{self, Plan, PlanEntry};
+use crate::policy;
+use crate::remove::RemoveFailure;
+use crate::scanner::{scan_directory, CancelToken, ScanOptions, ScanProgress};
+use crate::snapshot::{self, SnapshotInfo};
+use crate::sunburst::{
+    draw_owner_legend, draw_sunburst, find_segment_at_point, get_ring_width, ColorMode,
+    OWNER_COLORS,
+};
+use crate::tree::{Node, ScanTree};
+use crate::watch::{watch_tree, WatchEvent};
 
-use gtk4::gdk::Display;
-use gtk4::glib::{timeout_add_local, ControlFlow};
+use gtk4::gdk::{Display, Key, ModifierType, Rectangle};
+use gtk4::glib::{timeout_add_local, ControlFlow, Propagation};
 use gtk4::prelude::*;
 use gtk4::{
-    Align, Application, ApplicationWindow, Box as GtkBox, Button, CssProvider, DrawingArea,
-    FileChooserAction, FileChooserDialog, GestureClick, Label, MessageDialog, MessageType,
-    ButtonsType, Orientation, ProgressBar, ResponseType,
+    Align, Application, ApplicationWindow, Box as GtkBox, Button, ButtonsType, CssProvider,
+    DrawingArea, DropDown, EventControllerKey, FileChooserAction, FileChooserDialog, FileFilter,
+    GestureClick, Label, MessageDialog, MessageType, Orientation, Popover, ProgressBar,
+    ResponseType, ToggleButton,
 };
 use std::cell::RefCell;
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
 use std::time::Duration;
 
 pub fn build_ui(app: &Application) {
@@ -55,6 +70,10 @@ pub fn build_ui(app: &Application) {
             color: #666666;
             border-color: #442222;
         }
+        button:checked {
+            background-color: #7a3018;
+            border-color: #ffaa44;
+        }
         button label {
             color: #ffddcc;
         }
@@ -75,6 +94,16 @@ pub fn build_ui(app: &Application) {
             color: #ffff88;
             font-weight: bold;
         }
+        .details-panel {
+            background-color: #1f1010;
+            border-left: 1px solid #663322;
+            padding: 12px;
+        }
+        .details-label {
+            font-family: monospace;
+            font-size: 12px;
+            color: #ffddcc;
+        }
         .breadcrumb {
             background-color: #3d2020;
             padding: 4px 8px;
@@ -132,14 +161,75 @@ pub fn build_ui(app: &Application) {
     // Scan button
     let scan_btn = Button::with_label("IGNITE");
 
+    // Stop button (only shown while scanning)
+    let stop_btn = Button::with_label("EXTINGUISH");
+    stop_btn.set_visible(false);
+
     // Up button
     let up_btn = Button::with_label("↑ Escape");
     up_btn.set_sensitive(false);
 
+    // Batch delete button (only shown while something is marked)
+    let marked_btn = Button::with_label("BURN marked");
+    marked_btn.set_tooltip_text(Some("Ctrl+click segments to mark them for deletion"));
+    marked_btn.set_visible(false);
+
+    // Size mode: apparent bytes, allocated blocks or inodes used
+    let size_dropdown = DropDown::from_strings(&["Apparent size", "On disk", "Inodes"]);
+    size_dropdown.set_tooltip_text(Some("What the segments are sized by"));
+
+    // Side panel with everything known about the hovered entry
+    let details_btn = ToggleButton::with_label("Details");
+    details_btn.set_tooltip_text(Some(
+        "Show timestamps, owner and permissions of the hovered entry",
+    ));
+
+    // Follow changes on disk after the scan
+    let watch_btn = ToggleButton::with_label("Watch");
+    watch_btn.set_tooltip_text(Some("Keep the chart up to date as files change on disk"));
+
+    // Snapshot buttons
+    let open_btn = Button::with_label("Open");
+    open_btn.set_tooltip_text(Some("Load a saved scan snapshot"));
+    let save_btn = Button::with_label("Save");
+    save_btn.set_tooltip_text(Some("Save this scan as a snapshot"));
+    let compare_btn = Button::with_label("Compare");
+    compare_btn.set_tooltip_text(Some("Compare this scan against a saved snapshot"));
+
+    // Usage by user and group
+    let owners_btn = Button::with_label("Owners");
+    owners_btn.set_tooltip_text(Some("Show who owns what's in view"));
+
+    // Colour by file type, growth since the comparison snapshot, or age
+    let color_dropdown = DropDown::from_strings(&[
+        "Type colours",
+        "Growth colours",
+        "Age: newest file",
+        "Age: oldest file",
+        "Owner colours",
+    ]);
+
+    // Stay on one filesystem toggle
+    let one_fs_btn = ToggleButton::with_label("One FS");
+    one_fs_btn.set_tooltip_text(Some(
+        "Stop at mount points instead of scanning other filesystems",
+    ));
+
     header.append(&choose_btn);
     header.append(&path_label);
+    header.append(&one_fs_btn);
+    header.append(&size_dropdown);
+    header.append(&watch_btn);
+    header.append(&details_btn);
+    header.append(&open_btn);
+    header.append(&save_btn);
+    header.append(&compare_btn);
+    header.append(&owners_btn);
+    header.append(&color_dropdown);
     header.append(&up_btn);
+    header.append(&marked_btn);
     header.append(&scan_btn);
+    header.append(&stop_btn);
 
     // Breadcrumb bar
     let breadcrumb_box = GtkBox::new(Orientation::Horizontal, 4);
@@ -152,6 +242,24 @@ pub fn build_ui(app: &Application) {
     drawing_area.set_hexpand(true);
     drawing_area.set_vexpand(true);
 
+    // Details panel (hidden until toggled on)
+    let details_label = Label::new(None);
+    details_label.add_css_class("details-label");
+    details_label.set_xalign(0.0);
+    details_label.set_yalign(0.0);
+    details_label.set_wrap(true);
+    details_label.set_wrap_mode(gtk4::pango::WrapMode::Char);
+    details_label.set_selectable(true);
+    let details_panel = GtkBox::new(Orientation::Vertical, 0);
+    details_panel.add_css_class("details-panel");
+    details_panel.set_size_request(300, -1);
+    details_panel.append(&details_label);
+    details_panel.set_visible(false);
+
+    let body = GtkBox::new(Orientation::Horizontal, 0);
+    body.append(&drawing_area);
+    body.append(&details_panel);
+
     // Progress bar (hidden initially)
     let progress_bar = ProgressBar::new();
     progress_bar.add_css_class("scan-progress");
@@ -182,7 +290,7 @@ pub fn build_ui(app: &Application) {
     // Assemble UI
     main_box.append(&header);
     main_box.append(&breadcrumb_box);
-    main_box.append(&drawing_area);
+    main_box.append(&body);
     main_box.append(&progress_bar);
     main_box.append(&status_bar);
     window.set_child(Some(&main_box));
@@ -192,7 +300,38 @@ pub fn build_ui(app: &Application) {
     drawing_area.set_draw_func(move |_, cr, width, height| {
         let state = state_draw.borrow();
         let hover = state.hover_path.as_ref();
-        draw_sunburst(cr, &state.segments, width as f64, height as f64, hover);
+        draw_sunburst(
+            cr,
+            &state.segments,
+            width as f64,
+            height as f64,
+            hover,
+            state.color_mode,
+            state.size_mode,
+        );
+        if let Some(usage) = state
+            .view_owners
+            .as_ref()
+            .filter(|_| !state.segments.is_empty())
+        {
+            let mode = state.size_mode;
+            let label = |name: String, amount: u64| format!("{} - {}", name, mode.format(amount));
+            let owners: Vec<String> = usage
+                .users
+                .iter()
+                .take(OWNER_COLORS.len())
+                .map(|&(uid, usage)| label(state.owner_names.user(uid), usage.size_in(mode)))
+                .collect();
+            let others = usage
+                .users
+                .get(OWNER_COLORS.len()..)
+                .filter(|rest| !rest.is_empty());
+            let others = others.map(|rest| {
+                let amount = rest.iter().map(|(_, usage)| usage.size_in(mode)).sum();
+                label(plural(rest.len(), "other owner"), amount)
+            });
+            draw_owner_legend(cr, &owners, others.as_deref(), height as f64);
+        }
     });
 
     // Mouse motion for hover
@@ -200,6 +339,7 @@ pub fn build_ui(app: &Application) {
     let state_motion = state.clone();
     let drawing_area_motion = drawing_area.clone();
     let hover_label_motion = hover_label.clone();
+    let details_label_motion = details_label.clone();
     motion_ctrl.connect_motion(move |_, x, y| {
         let width = drawing_area_motion.width() as f64;
         let height = drawing_area_motion.height() as f64;
@@ -215,22 +355,48 @@ pub fn build_ui(app: &Application) {
                 width / 2.0,
                 height / 2.0,
                 ring_width,
-            ).map(|seg| (seg.path.clone(), seg.size))
+            ).cloned()
         };
 
         // Then mutate with mutable borrow
         let mut state = state_motion.borrow_mut();
-        if let Some((path, size)) = found {
-            state.hover_path = Some(path.clone());
+        if let Some(segment) = found {
+            let mode = state.size_mode;
+            let details = if segment.is_file && segment.nlink > 1 {
+                format!(" · {} hard links", segment.nlink)
+            } else if segment.is_file {
+                String::new()
+            } else {
+                // The directory itself isn't one of its folders
+                let folders = segment.dirs - 1;
+                format!(" · {} files, {} folders", segment.files, folders)
+            };
+            let growth = match segment.change {
+                Some((Change::Unchanged, _)) => " · unchanged".to_string(),
+                Some((change, delta)) => {
+                    format!(" · {} {}", format_delta(delta, mode), change.label())
+                }
+                None => String::new(),
+            };
+            let modified = if segment.stat.is_recorded() {
+                format!(" · modified {}", format_time(segment.stat.mtime))
+            } else {
+                String::new()
+            };
             hover_label_motion.set_text(&format!(
-                "{} ({})",
-                path.display(),
-                format_size(size)
+                "{} ({}){}{}{}",
+                segment.path.display(),
+                mode.format(segment.size),
+                details,
+                modified,
+                growth
             ));
+            state.hover_path = Some(segment.path);
         } else {
             state.hover_path = None;
             hover_label_motion.set_text("");
         }
+        update_details(&details_label_motion, &state);
         drop(state);
         drawing_area_motion.queue_draw();
     });
@@ -246,7 +412,8 @@ pub fn build_ui(app: &Application) {
     let state_bc = state.clone();
     let drawing_area_bc = drawing_area.clone();
     let up_btn_bc = up_btn.clone();
-    click_ctrl.connect_released(move |_, _, x, y| {
+    let marked_btn_click = marked_btn.clone();
+    click_ctrl.connect_released(move |gesture, _, x, y| {
         let width = drawing_area_click.width() as f64;
         let height = drawing_area_click.height() as f64;
         let ring_width = get_ring_width(width, height);
@@ -264,6 +431,22 @@ pub fn build_ui(app: &Application) {
             ).map(|seg| (seg.depth, seg.is_file, seg.path.clone()))
         };
 
+        // Ctrl+click marks entries for batch deletion instead of navigating
+        if gesture
+            .current_event_state()
+            .contains(ModifierType::CONTROL_MASK)
+        {
+            if let Some((depth, _, path)) = found {
+                let mut state = state_click.borrow_mut();
+                if depth > 0 && !is_protected_path(&path) && !state.scanning {
+                    state.toggle_mark(path);
+                    update_marked_button(&marked_btn_click, &state);
+                }
+            }
+            drawing_area_click.queue_draw();
+            return;
+        }
+
         if let Some((depth, is_file, path)) = found {
             let mut state = state_click.borrow_mut();
             if depth == 0 {
@@ -288,12 +471,16 @@ pub fn build_ui(app: &Application) {
     });
     drawing_area.add_controller(click_ctrl);
 
-    // Right click for context menu (delete)
+    // Right click for a context menu: refresh or delete
     let right_click_ctrl = GestureClick::new();
     right_click_ctrl.set_button(3); // Right click
     let state_rclick = state.clone();
     let drawing_area_rclick = drawing_area.clone();
     let window_rclick = window.clone();
+    let status_label_rclick = status_label.clone();
+    let marked_btn_rclick = marked_btn.clone();
+    let up_btn_rclick = up_btn.clone();
+    let breadcrumb_box_rclick = breadcrumb_box.clone();
     right_click_ctrl.connect_released(move |_, _, x, y| {
         let width = drawing_area_rclick.width() as f64;
         let height = drawing_area_rclick.height() as f64;
@@ -309,28 +496,222 @@ pub fn build_ui(app: &Application) {
                 width / 2.0,
                 height / 2.0,
                 ring_width,
-            ).map(|seg| (seg.depth, seg.path.clone(), seg.name.clone(), seg.size, seg.is_file))
+            ).map(|seg| (seg.depth, seg.path.clone(), seg.size, seg.is_file))
         };
 
-        if let Some((depth, path, name, size, is_file)) = found {
-            // Don't allow deleting the center (view root) or protected paths
-            if depth == 0 || is_protected_path(&path) {
-                return;
-            }
+        let Some((depth, path, size, is_file)) = found else {
+            return;
+        };
 
+        // Both actions report back by updating everything the tree feeds
+        let state = state_rclick.clone();
+        let drawing_area = drawing_area_rclick.clone();
+        let status_label = status_label_rclick.clone();
+        let marked_btn = marked_btn_rclick.clone();
+        let up_btn = up_btn_rclick.clone();
+        let breadcrumb_box = breadcrumb_box_rclick.clone();
+        let on_changed: Rc<dyn Fn(&str)> = Rc::new(move |message| {
+            status_label.set_text(message);
+            let s = state.borrow();
+            update_marked_button(&marked_btn, &s);
+            up_btn.set_sensitive(s.can_navigate_up());
+            update_breadcrumbs(
+                &breadcrumb_box,
+                &s.get_breadcrumbs(),
+                state.clone(),
+                drawing_area.clone(),
+                up_btn.clone(),
+            );
+            drop(s);
+            drawing_area.queue_draw();
+        });
+
+        // The tree is about to be replaced by the scan result
+        let scanning = state_rclick.borrow().scanning;
+        let refresh_btn = Button::with_label(if is_file {
+            "Refresh this file"
+        } else {
+            "Refresh this folder"
+        });
+        refresh_btn.set_sensitive(!scanning);
+        // Don't allow deleting the center (view root) or protected paths
+        let rule = policy::global().protecting_rule(&path);
+        let burn_btn = match rule {
+            Some(rule) => Button::with_label(&format!("Protected ({})", rule.source)),
+            None => Button::with_label("BURN..."),
+        };
+        burn_btn.set_sensitive(depth > 0 && !scanning && rule.is_none());
+
+        let menu = GtkBox::new(Orientation::Vertical, 0);
+        for btn in [&refresh_btn, &burn_btn] {
+            btn.add_css_class("flat");
+            menu.append(btn);
+        }
+        let popover = Popover::new();
+        popover.set_child(Some(&menu));
+        popover.set_parent(&drawing_area_rclick);
+        popover.set_pointing_to(Some(&Rectangle::new(x as i32, y as i32, 1, 1)));
+        popover.connect_closed(|popover| popover.unparent());
+
+        let popover_refresh = popover.clone();
+        let state_refresh = state_rclick.clone();
+        let path_refresh = path.clone();
+        let on_refreshed = on_changed.clone();
+        refresh_btn.connect_clicked(move |_| {
+            popover_refresh.popdown();
+            refresh_subtree(
+                path_refresh.clone(),
+                state_refresh.clone(),
+                on_refreshed.clone(),
+            );
+        });
+
+        let popover_burn = popover.clone();
+        let state_burn = state_rclick.clone();
+        let window_burn = window_rclick.clone();
+        burn_btn.connect_clicked(move |_| {
+            popover_burn.popdown();
             show_delete_dialog(
-                &window_rclick,
-                path,
-                name,
+                &window_burn,
+                path.clone(),
                 size,
                 is_file,
-                state_rclick.clone(),
-                drawing_area_rclick.clone(),
+                state_burn.clone(),
+                on_changed.clone(),
             );
-        }
+        });
+
+        popover.popup();
     });
     drawing_area.add_controller(right_click_ctrl);
 
+    // Size mode
+    let state_size = state.clone();
+    let drawing_area_size = drawing_area.clone();
+    let marked_btn_size = marked_btn.clone();
+    size_dropdown.connect_selected_notify(move |dropdown| {
+        let mode = match dropdown.selected() {
+            1 => SizeMode::Allocated,
+            2 => SizeMode::Inodes,
+            _ => SizeMode::Apparent,
+        };
+        let mut state = state_size.borrow_mut();
+        state.set_size_mode(mode);
+        update_marked_button(&marked_btn_size, &state);
+        drop(state);
+        drawing_area_size.queue_draw();
+    });
+
+    // Colour mode
+    let state_color = state.clone();
+    let drawing_area_color = drawing_area.clone();
+    color_dropdown.connect_selected_notify(move |dropdown| {
+        state_color
+            .borrow_mut()
+            .set_color_mode(match dropdown.selected() {
+                1 => ColorMode::Growth,
+                2 => ColorMode::Newest,
+                3 => ColorMode::Oldest,
+                4 => ColorMode::Owner,
+                _ => ColorMode::FileType,
+            });
+        drawing_area_color.queue_draw();
+    });
+
+    // One filesystem toggle
+    let state_one_fs = state.clone();
+    one_fs_btn.connect_toggled(move |btn| {
+        state_one_fs.borrow_mut().scan_options.one_file_system = btn.is_active();
+    });
+
+    // Details toggle
+    let state_details = state.clone();
+    let details_label_toggle = details_label.clone();
+    details_btn.connect_toggled(move |btn| {
+        details_panel.set_visible(btn.is_active());
+        update_details(&details_label_toggle, &state_details.borrow());
+    });
+
+    // Watch toggle: apply changes on disk to the tree until switched off
+    let state_watch = state.clone();
+    let status_label_watch = status_label.clone();
+    let drawing_area_watch = drawing_area.clone();
+    let marked_btn_watch = marked_btn.clone();
+    let up_btn_watch = up_btn.clone();
+    let breadcrumb_box_watch = breadcrumb_box.clone();
+    watch_btn.connect_toggled(move |btn| {
+        let mut state = state_watch.borrow_mut();
+        if let Some(cancel) = state.watch_cancel.take() {
+            cancel.cancel();
+        }
+        if !btn.is_active() {
+            return;
+        }
+        // Nothing to follow yet, or a tree still streaming in
+        let root = state
+            .scan_root
+            .as_ref()
+            .map(|root| root.root_path().to_path_buf());
+        let Some(root) = root.filter(|_| !state.scanning) else {
+            drop(state);
+            btn.set_active(false);
+            return;
+        };
+
+        let cancel = CancelToken::new();
+        state.watch_cancel = Some(cancel.clone());
+        let rx = watch_tree(root.clone(), state.scan_options.clone(), cancel.clone());
+        drop(state);
+        status_label_watch.set_text(&format!("Watching {} for changes", root.display()));
+
+        let state = state_watch.clone();
+        let status_label = status_label_watch.clone();
+        let drawing_area = drawing_area_watch.clone();
+        let marked_btn = marked_btn_watch.clone();
+        let up_btn = up_btn_watch.clone();
+        let breadcrumb_box = breadcrumb_box_watch.clone();
+        let btn = btn.clone();
+        timeout_add_local(Duration::from_millis(100), move || {
+            if cancel.is_cancelled() {
+                return ControlFlow::Break;
+            }
+
+            // Apply everything that arrived since the last tick in one go
+            let mut events = Vec::new();
+            loop {
+                match rx.try_recv() {
+                    Ok(WatchEvent::Degraded(message)) => status_label.set_text(&message),
+                    Ok(WatchEvent::Error(message)) => {
+                        status_label.set_text(&message);
+                        btn.set_active(false);
+                        return ControlFlow::Break;
+                    }
+                    Ok(event) => events.push(event),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                }
+            }
+            if events.is_empty() {
+                return ControlFlow::Continue;
+            }
+
+            let mut s = state.borrow_mut();
+            s.apply_watch_events(events);
+            update_marked_button(&marked_btn, &s);
+            up_btn.set_sensitive(s.can_navigate_up());
+            update_breadcrumbs(
+                &breadcrumb_box,
+                &s.get_breadcrumbs(),
+                state.clone(),
+                drawing_area.clone(),
+                up_btn.clone(),
+            );
+            drop(s);
+            drawing_area.queue_draw();
+            ControlFlow::Continue
+        });
+    });
+
     // Directory chooser
     let state_choose = state.clone();
     let path_label_choose = path_label.clone();
@@ -359,6 +740,262 @@ pub fn build_ui(app: &Application) {
         dialog.show();
     });
 
+    // Save snapshot
+    let state_save = state.clone();
+    let status_label_save = status_label.clone();
+    let window_save = window.clone();
+    save_btn.connect_clicked(move |_| {
+        let default_name = match &state_save.borrow().scan_root {
+            Some(tree) => {
+                let name = tree.name(tree.root());
+                format!("{}.{}", name.trim_matches('/'), snapshot::EXTENSION)
+            }
+            None => {
+                status_label_save.set_text("Nothing scorched yet - nothing to save");
+                return;
+            }
+        };
+
+        let dialog = FileChooserDialog::new(
+            Some("Save Snapshot"),
+            Some(&window_save),
+            FileChooserAction::Save,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Save", ResponseType::Accept),
+            ],
+        );
+        dialog.set_current_name(&default_name);
+        dialog.add_filter(&snapshot_filter());
+
+        let state = state_save.clone();
+        let status_label = status_label_save.clone();
+        dialog.connect_response(move |dialog, response| {
+            if response == ResponseType::Accept {
+                if let Some(file) = dialog.file().and_then(|f| f.path()) {
+                    let state = state.borrow();
+                    if let Some(tree) = &state.scan_root {
+                        let info =
+                            SnapshotInfo::new(tree.root_path().to_path_buf(), state.size_mode);
+                        match snapshot::save(&file, tree, &info) {
+                            Ok(()) => status_label
+                                .set_text(&format!("Snapshot saved to {}", file.display())),
+                            Err(e) => status_label.set_text(&format!("Error: {}", e)),
+                        }
+                    }
+                }
+            }
+            dialog.close();
+        });
+        dialog.show();
+    });
+
+    // Open snapshot
+    let state_open = state.clone();
+    let status_label_open = status_label.clone();
+    let path_label_open = path_label.clone();
+    let size_dropdown_open = size_dropdown.clone();
+    let drawing_area_open = drawing_area.clone();
+    let up_btn_open = up_btn.clone();
+    let breadcrumb_box_open = breadcrumb_box.clone();
+    let marked_btn_open = marked_btn.clone();
+    let watch_btn_open = watch_btn.clone();
+    let window_open = window.clone();
+    open_btn.connect_clicked(move |_| {
+        if state_open.borrow().scanning {
+            return;
+        }
+
+        let dialog = FileChooserDialog::new(
+            Some("Open Snapshot"),
+            Some(&window_open),
+            FileChooserAction::Open,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Open", ResponseType::Accept),
+            ],
+        );
+        dialog.add_filter(&snapshot_filter());
+
+        let state = state_open.clone();
+        let status_label = status_label_open.clone();
+        let path_label = path_label_open.clone();
+        let size_dropdown = size_dropdown_open.clone();
+        let drawing_area = drawing_area_open.clone();
+        let up_btn = up_btn_open.clone();
+        let breadcrumb_box = breadcrumb_box_open.clone();
+        let marked_btn = marked_btn_open.clone();
+        let watch_btn = watch_btn_open.clone();
+        dialog.connect_response(move |dialog, response| {
+            let file = dialog.file().and_then(|f| f.path());
+            dialog.close();
+            let file = match file {
+                Some(file) if response == ResponseType::Accept => file,
+                _ => return,
+            };
+
+            // Big snapshots take a moment to decode, so read off the UI thread
+            status_label.set_text(&format!("Rekindling {}...", file.display()));
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || {
+                let _ = tx.send(snapshot::load(&file));
+            });
+
+            let state = state.clone();
+            let status_label = status_label.clone();
+            let path_label = path_label.clone();
+            let size_dropdown = size_dropdown.clone();
+            let drawing_area = drawing_area.clone();
+            let up_btn = up_btn.clone();
+            let breadcrumb_box = breadcrumb_box.clone();
+            let marked_btn = marked_btn.clone();
+            let watch_btn = watch_btn.clone();
+            timeout_add_local(Duration::from_millis(50), move || {
+                let loaded = match rx.try_recv() {
+                    Ok(loaded) => loaded,
+                    Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+                    Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                };
+
+                let (info, tree) = match loaded {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        status_label.set_text(&format!("Error: {}", e));
+                        return ControlFlow::Break;
+                    }
+                };
+
+                // A snapshot is a record of the past, not something to follow
+                watch_btn.set_active(false);
+
+                // Adopt the snapshot's size mode before installing the tree
+                size_dropdown.set_selected(match info.size_mode {
+                    SizeMode::Apparent => 0,
+                    SizeMode::Allocated => 1,
+                    SizeMode::Inodes => 2,
+                });
+
+                let mut s = state.borrow_mut();
+                let root = tree.get(tree.root());
+                let item_count = root.item_count();
+                let total_size = root.size_in(info.size_mode);
+                s.view_root = tree.root_path().to_path_buf();
+                s.cancel_refreshes();
+                s.set_scan_root(tree);
+                path_label.set_text(&info.root.to_string_lossy());
+                status_label.set_text(&format!(
+                    "Snapshot of {} on {}, taken {} - {} items, {} ablaze",
+                    info.root.display(),
+                    info.hostname,
+                    format_timestamp(info.timestamp),
+                    item_count,
+                    info.size_mode.format(total_size)
+                ));
+                update_marked_button(&marked_btn, &s);
+                up_btn.set_sensitive(s.can_navigate_up());
+                update_breadcrumbs(
+                    &breadcrumb_box,
+                    &s.get_breadcrumbs(),
+                    state.clone(),
+                    drawing_area.clone(),
+                    up_btn.clone(),
+                );
+                drop(s);
+                drawing_area.queue_draw();
+                ControlFlow::Break
+            });
+        });
+        dialog.show();
+    });
+
+    // Owners of the current view
+    let state_owners = state.clone();
+    let window_owners = window.clone();
+    owners_btn.connect_clicked(move |_| {
+        show_owners_dialog(&window_owners, &state_owners.borrow());
+    });
+
+    // Compare against a snapshot
+    let state_compare = state.clone();
+    let status_label_compare = status_label.clone();
+    let color_dropdown_compare = color_dropdown.clone();
+    let drawing_area_compare = drawing_area.clone();
+    let window_compare = window.clone();
+    compare_btn.connect_clicked(move |_| {
+        let dialog = FileChooserDialog::new(
+            Some("Compare With Snapshot"),
+            Some(&window_compare),
+            FileChooserAction::Open,
+            &[
+                ("Cancel", ResponseType::Cancel),
+                ("Compare", ResponseType::Accept),
+            ],
+        );
+        dialog.add_filter(&snapshot_filter());
+
+        let state = state_compare.clone();
+        let status_label = status_label_compare.clone();
+        let color_dropdown = color_dropdown_compare.clone();
+        let drawing_area = drawing_area_compare.clone();
+        dialog.connect_response(move |dialog, response| {
+            let file = dialog.file().and_then(|f| f.path());
+            dialog.close();
+            let file = match file {
+                Some(file) if response == ResponseType::Accept => file,
+                _ => return,
+            };
+
+            status_label.set_text(&format!("Digging up {}...", file.display()));
+            let (tx, rx) = mpsc::channel();
+            thread::spawn(move || {
+                let _ = tx.send(snapshot::load(&file));
+            });
+
+            let state = state.clone();
+            let status_label = status_label.clone();
+            let color_dropdown = color_dropdown.clone();
+            let drawing_area = drawing_area.clone();
+            timeout_add_local(Duration::from_millis(50), move || {
+                let loaded = match rx.try_recv() {
+                    Ok(loaded) => loaded,
+                    Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+                    Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                };
+
+                let (info, tree) = match loaded {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        status_label.set_text(&format!("Error: {}", e));
+                        return ControlFlow::Break;
+                    }
+                };
+
+                let mut s = state.borrow_mut();
+                s.set_baseline(tree);
+                let mut status = format!(
+                    "Comparing with {} as of {}",
+                    info.root.display(),
+                    format_timestamp(info.timestamp)
+                );
+                match &s.diff {
+                    Some(diff) => {
+                        status.push_str(" - ");
+                        status.push_str(&diff_summary(diff, s.size_mode));
+                    }
+                    None => status.push_str(" - IGNITE to see what changed"),
+                }
+                status_label.set_text(&status);
+                drop(s);
+
+                // Triggers a redraw through the dropdown handler
+                color_dropdown.set_selected(1);
+                drawing_area.queue_draw();
+                ControlFlow::Break
+            });
+        });
+        dialog.show();
+    });
+
     // Up button
     let state_up = state.clone();
     let drawing_area_up = drawing_area.clone();
@@ -383,53 +1020,205 @@ pub fn build_ui(app: &Application) {
         drawing_area_up.queue_draw();
     });
 
+    // Batch delete
+    let state_marked = state.clone();
+    let status_label_marked = status_label.clone();
+    let drawing_area_marked = drawing_area.clone();
+    let window_marked = window.clone();
+    marked_btn.connect_clicked(move |btn| {
+        if state_marked.borrow().scanning {
+            return;
+        }
+        let state = state_marked.clone();
+        let status_label = status_label_marked.clone();
+        let drawing_area = drawing_area_marked.clone();
+        let btn = btn.clone();
+        show_batch_delete_dialog(&window_marked, state_marked.clone(), move |message| {
+            status_label.set_text(message);
+            update_marked_button(&btn, &state.borrow());
+            drawing_area.queue_draw();
+        });
+    });
+
+    // Ctrl+Z restores the last trashed entry
+    let key_ctrl = EventControllerKey::new();
+    let state_undo = state.clone();
+    let status_label_undo = status_label.clone();
+    let drawing_area_undo = drawing_area.clone();
+    let marked_btn_undo = marked_btn.clone();
+    key_ctrl.connect_key_pressed(move |_, key, _, modifiers| {
+        if key != Key::z || !modifiers.contains(ModifierType::CONTROL_MASK) {
+            return Propagation::Proceed;
+        }
+
+        let mut state = state_undo.borrow_mut();
+        // The tree is about to be replaced by the scan result
+        if state.scanning {
+            return Propagation::Stop;
+        }
+        match state.undo() {
+            Some(Ok(path)) => {
+                status_label_undo.set_text(&format!("{} rose from the ashes", path.display()))
+            }
+            Some(Err(e)) => status_label_undo.set_text(&format!("Error: {}", e)),
+            None => status_label_undo.set_text("Nothing to undo"),
+        }
+        update_marked_button(&marked_btn_undo, &state);
+        drop(state);
+        drawing_area_undo.queue_draw();
+        Propagation::Stop
+    });
+    window.add_controller(key_ctrl);
+
+    // Stop button
+    let state_stop = state.clone();
+    let status_label_stop = status_label.clone();
+    stop_btn.connect_clicked(move |btn| {
+        if let Some(cancel) = &state_stop.borrow().scan_cancel {
+            cancel.cancel();
+            btn.set_sensitive(false);
+            status_label_stop.set_text("Extinguishing...");
+        }
+    });
+
     // Scan button
     let state_scan = state.clone();
     let status_label_scan = status_label.clone();
     let drawing_area_scan = drawing_area.clone();
     let up_btn_scan = up_btn.clone();
     let scan_btn_scan = scan_btn.clone();
+    let stop_btn_scan = stop_btn.clone();
     let breadcrumb_box_scan = breadcrumb_box.clone();
     let state_bc_scan = state.clone();
     let drawing_area_bc_scan = drawing_area.clone();
     let up_btn_bc_scan = up_btn.clone();
+    let marked_btn_scan = marked_btn.clone();
     let progress_bar_scan = progress_bar.clone();
+    let window_scan = window.clone();
+    let watch_btn_scan = watch_btn.clone();
     scan_btn.connect_clicked(move |_| {
+        // The watch follows the tree that's about to be replaced
+        watch_btn_scan.set_active(false);
+
         let mut state = state_scan.borrow_mut();
         if state.scanning {
             return;
         }
 
+        let cancel = CancelToken::new();
+        state.cancel_refreshes();
         state.scanning = true;
+        state.scan_cancel = Some(cancel.clone());
         state.items_scanned = 0;
         let path = state.view_root.clone();
+        let options = state.scan_options.clone();
+
+        // The old tree gives way to the one streaming in
+        state.scan_root = None;
+        state.diff = None;
+        state.segments.clear();
         drop(state);
+        drawing_area_scan.queue_draw();
 
         scan_btn_scan.set_sensitive(false);
+        stop_btn_scan.set_sensitive(true);
+        stop_btn_scan.set_visible(true);
         status_label_scan.set_text(&format!("Burning through {}...", path.display()));
         progress_bar_scan.set_visible(true);
         progress_bar_scan.set_fraction(0.0);
         progress_bar_scan.set_text(Some("Igniting..."));
         progress_bar_scan.set_show_text(true);
 
-        let rx = scan_directory(path.clone());
+        let rx = scan_directory(path.clone(), options, cancel);
 
         let state = state_scan.clone();
         let status_label = status_label_scan.clone();
+        let scan_btn = scan_btn_scan.clone();
+        let stop_btn = stop_btn_scan.clone();
+        let progress_bar = progress_bar_scan.clone();
+        let window = window_scan.clone();
+        let drawing_area_live = drawing_area_scan.clone();
+        let up_btn_live = up_btn_scan.clone();
+        let breadcrumb_box_live = breadcrumb_box_scan.clone();
+        let state_bc_live = state_bc_scan.clone();
+        let drawing_area_bc_live = drawing_area_bc_scan.clone();
+        let up_btn_bc_live = up_btn_bc_scan.clone();
+
+        // Reports on the finished (or partial) tree once it is installed
+        let state_result = state.clone();
+        let status_label_result = status_label.clone();
         let drawing_area = drawing_area_scan.clone();
         let up_btn = up_btn_scan.clone();
-        let scan_btn = scan_btn_scan.clone();
         let breadcrumb_box = breadcrumb_box_scan.clone();
         let state_bc = state_bc_scan.clone();
         let drawing_area_bc = drawing_area_bc_scan.clone();
         let up_btn_bc = up_btn_bc_scan.clone();
-        let progress_bar = progress_bar_scan.clone();
+        let marked_btn = marked_btn_scan.clone();
+        let show_result = Rc::new(move |partial: bool| {
+            let state = state_result.borrow();
+            let Some(tree) = &state.scan_root else {
+                return;
+            };
+            let root = tree.get(tree.root());
+            let total_size = root.size;
+            let disk_size = root.disk_size;
+            let item_count = root.item_count();
+            let skipped_mounts = tree.skipped_mount_points().len();
+
+            let mut status = format!(
+                "{} {} items - {} ablaze ({} on disk)",
+                if partial {
+                    "Partially scorched"
+                } else {
+                    "Scorched"
+                },
+                item_count,
+                format_size(total_size),
+                format_size(disk_size)
+            );
+            if skipped_mounts > 0 {
+                status.push_str(&format!(" - {} mount points not traversed", skipped_mounts));
+            }
+            if let Some(diff) = &state.diff {
+                status.push_str(" - ");
+                status.push_str(&diff_summary(diff, state.size_mode));
+            }
+            status_label_result.set_text(&status);
+            update_marked_button(&marked_btn, &state);
+            up_btn.set_sensitive(state.can_navigate_up());
+
+            update_breadcrumbs(
+                &breadcrumb_box,
+                &state.get_breadcrumbs(),
+                state_bc.clone(),
+                drawing_area_bc.clone(),
+                up_btn_bc.clone(),
+            );
+
+            drop(state);
+            drawing_area.queue_draw();
+        });
 
         timeout_add_local(Duration::from_millis(50), move || {
             // Pulse progress bar to show activity
             progress_bar.pulse();
 
+            let mut grew = false;
             while let Ok(progress) = rx.try_recv() {
+                // Any terminal message ends the scan
+                if matches!(
+                    progress,
+                    ScanProgress::Complete(_) | ScanProgress::Cancelled(_) | ScanProgress::Error(_)
+                ) {
+                    let mut s = state.borrow_mut();
+                    s.scanning = false;
+                    s.scan_cancel = None;
+                    drop(s);
+                    progress_bar.set_visible(false);
+                    scan_btn.set_sensitive(true);
+                    stop_btn.set_visible(false);
+                }
+
                 match progress {
                     ScanProgress::Scanning(_) => {
                         // Update less frequently to reduce UI overhead
@@ -439,45 +1228,61 @@ pub fn build_ui(app: &Application) {
                         status_label.set_text(&format!("Scorching... {} items consumed", count));
                         progress_bar.set_text(Some(&format!("{} items burned", count)));
                     }
-                    ScanProgress::Complete(entry) => {
-                        let mut state = state.borrow_mut();
-                        let total_size = entry.total_size();
-                        let item_count = entry.item_count();
-                        state.view_root = entry.path.clone();
-                        state.scan_root = Some(entry);
-                        state.rebuild_segments();
-                        state.scanning = false;
-
-                        status_label.set_text(&format!(
-                            "Scorched {} items - {} ablaze",
-                            item_count,
-                            format_size(total_size)
-                        ));
-                        progress_bar.set_visible(false);
-                        scan_btn.set_sensitive(true);
-                        up_btn.set_sensitive(state.can_navigate_up());
-
-                        update_breadcrumbs(
-                            &breadcrumb_box,
-                            &state.get_breadcrumbs(),
-                            state_bc.clone(),
-                            drawing_area_bc.clone(),
-                            up_btn_bc.clone(),
-                        );
-
-                        drop(state);
-                        drawing_area.queue_draw();
+                    ScanProgress::Subtree(subtree) => {
+                        state.borrow_mut().add_scanned_subtree(&path, subtree);
+                        grew = true;
+                    }
+                    ScanProgress::Complete(rest) => {
+                        // Stays where the user navigated to during a live scan
+                        state.borrow_mut().finish_scan(rest);
+                        show_result(false);
+                        return ControlFlow::Break;
+                    }
+                    ScanProgress::Cancelled(rest) => {
+                        status_label.set_text("Scan extinguished");
+                        state.borrow_mut().finish_scan(rest);
+                        drawing_area_live.queue_draw();
+                        let state_discard = state.clone();
+                        let drawing_area_discard = drawing_area_live.clone();
+                        if let Some(tree) = &state.borrow().scan_root {
+                            show_keep_partial_dialog(
+                                &window,
+                                tree,
+                                show_result.clone(),
+                                move || {
+                                    // Drop whatever streamed in live, too
+                                    let mut state = state_discard.borrow_mut();
+                                    state.scan_root = None;
+                                    state.segments.clear();
+                                    drop(state);
+                                    drawing_area_discard.queue_draw();
+                                },
+                            );
+                        }
                         return ControlFlow::Break;
                     }
                     ScanProgress::Error(e) => {
-                        state.borrow_mut().scanning = false;
                         status_label.set_text(&format!("Error: {}", e));
-                        progress_bar.set_visible(false);
-                        scan_btn.set_sensitive(true);
                         return ControlFlow::Break;
                     }
                 }
             }
+
+            // Redraw once per tick however many subtrees arrived
+            if grew {
+                let mut state = state.borrow_mut();
+                state.rebuild_segments();
+                up_btn_live.set_sensitive(state.can_navigate_up());
+                update_breadcrumbs(
+                    &breadcrumb_box_live,
+                    &state.get_breadcrumbs(),
+                    state_bc_live.clone(),
+                    drawing_area_bc_live.clone(),
+                    up_btn_bc_live.clone(),
+                );
+                drop(state);
+                drawing_area_live.queue_draw();
+            }
             ControlFlow::Continue
         });
     });
@@ -485,6 +1290,26 @@ pub fn build_ui(app: &Application) {
     window.present();
 }
 
+/// File chooser filter for snapshot files
+fn snapshot_filter() -> FileFilter {
+    let filter = FileFilter::new();
+    filter.set_name(Some("Scorch snapshots"));
+    filter.add_pattern(&format!("*.{}", snapshot::EXTENSION));
+    filter
+}
+
+/// One-line summary of what changed since the baseline
+fn diff_summary(diff: &DeltaEntry, mode: SizeMode) -> String {
+    format!(
+        "{} since then: {} added, {} grown, {} shrunk, {} removed",
+        format_delta(diff.delta(), mode),
+        diff.count(Change::Added),
+        diff.count(Change::Grown),
+        diff.count(Change::Shrunk),
+        diff.count(Change::Removed)
+    )
+}
+
 fn update_breadcrumbs(
     container: &GtkBox,
     crumbs: &[(PathBuf, String)],
@@ -529,20 +1354,125 @@ fn update_breadcrumbs(
     }
 }
 
+/// Show the hovered entry, or else the one being viewed, in the details panel
+fn update_details(label: &Label, state: &AppState) {
+    let path = state.hover_path.as_ref().unwrap_or(&state.view_root);
+    match state.find_entry(path) {
+        Some(node) => label.set_text(&describe_details(path, node, &state.owner_names)),
+        None => label.set_text("Nothing scorched yet"),
+    }
+}
+
+/// Everything known about one entry, a fact per line
+fn describe_details(path: &Path, node: &Node, names: &OwnerNames) -> String {
+    let name = path.file_name().unwrap_or(path.as_os_str());
+    let mut lines = vec![
+        name.to_string_lossy().into_owned(),
+        path.display().to_string(),
+        String::new(),
+        format!("Size      {}", format_size(node.size)),
+        format!("On disk   {}", format_size(node.disk_size)),
+        format!("Inodes    {}", node.inodes),
+    ];
+    if node.is_file {
+        lines.push(format!("Links     {}", node.nlink));
+    } else {
+        lines.push(format!("Files     {}", node.files));
+        lines.push(format!("Folders   {}", node.dirs - 1));
+    }
+    lines.push(String::new());
+
+    let stat = &node.stat;
+    if stat.is_recorded() {
+        lines.push(format!("Modified  {}", format_time(stat.mtime)));
+        lines.push(format!("Accessed  {}", format_time(stat.atime)));
+        lines.push(format!("Changed   {}", format_time(stat.ctime)));
+        if let Some((oldest, newest)) = node.mtimes().filter(|_| !node.is_file) {
+            lines.push(format!("Newest    {}", format_time(newest)));
+            lines.push(format!("Oldest    {}", format_time(oldest)));
+        }
+        lines.push(format!(
+            "Owner     {} ({})",
+            names.user(Some(stat.uid)),
+            stat.uid
+        ));
+        lines.push(format!(
+            "Group     {} ({})",
+            names.group(Some(stat.gid)),
+            stat.gid
+        ));
+        lines.push(format!(
+            "Mode      {} ({:04o})",
+            format_mode(stat.mode),
+            stat.mode & 0o7777
+        ));
+    } else {
+        lines.push("Timestamps and owner weren't recorded in this snapshot".to_string());
+    }
+    lines.join("\n")
+}
+
+/// A `Stat` time as a UTC date; anything before 1970 shows as the epoch
+fn format_time(secs: i64) -> String {
+    format_timestamp(secs.max(0) as u64)
+}
+
+/// Ask whether to keep the partial tree of a cancelled scan
+fn show_keep_partial_dialog(
+    window: &ApplicationWindow,
+    tree: &ScanTree,
+    show_result: Rc<dyn Fn(bool)>,
+    discard: impl Fn() + 'static,
+) {
+    let root = tree.get(tree.root());
+    let message = format!(
+        "Scan extinguished after {} items ({}).\n\nKeep the partial results?",
+        root.item_count(),
+        format_size(root.size)
+    );
+
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Question,
+        ButtonsType::None,
+        &message,
+    );
+    dialog.add_buttons(&[
+        ("Discard", ResponseType::Cancel),
+        ("Keep", ResponseType::Accept),
+    ]);
+
+    dialog.connect_response(move |dialog, response| {
+        if response == ResponseType::Accept {
+            show_result(true);
+        } else {
+            discard();
+        }
+        dialog.close();
+    });
+
+    dialog.show();
+}
+
 fn show_delete_dialog(
     window: &ApplicationWindow,
     path: PathBuf,
-    name: String,
     size: u64,
     is_file: bool,
     state: Rc<RefCell<AppState>>,
-    drawing_area: DrawingArea,
+    on_deleted: Rc<dyn Fn(&str)>,
 ) {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string());
     let message = format!(
-        "INCINERATE {}?\n\nTarget: {}\nSize: {}\nType: {}\n\nThis will be reduced to ashes!",
+        "INCINERATE {}?\n\nTarget: {}\nSize: {}\nType: {}\n\n\
+         BURN IT moves it to the Trash. Incinerate forever reduces it to ashes - no coming back!",
         name,
         path.display(),
-        format_size(size),
+        state.borrow().size_mode.format(size),
         if is_file { "File" } else { "Directory" }
     );
 
@@ -553,53 +1483,692 @@ fn show_delete_dialog(
         ButtonsType::None,
         &message,
     );
-    dialog.add_buttons(&[("Spare", ResponseType::Cancel), ("BURN IT", ResponseType::Accept)]);
+    dialog.add_buttons(&[
+        ("Spare", ResponseType::Cancel),
+        ("Incinerate forever", ResponseType::Reject),
+        ("BURN IT", ResponseType::Accept),
+    ]);
 
+    let window = window.clone();
     dialog.connect_response(move |dialog, response| {
-        if response == ResponseType::Accept {
-            // Delete confirmed
-            match delete_entry(&path) {
-                DeleteResult::Success => {
-                    // Update tree
-                    let mut s = state.borrow_mut();
-                    if let Some(root) = &mut s.scan_root {
-                        remove_entry_from_tree(root, &path);
+        dialog.close();
+        let mode = match response {
+            ResponseType::Accept => DeleteMode::Trash,
+            ResponseType::Reject => DeleteMode::Permanent,
+            _ => return,
+        };
+
+        // A big permanent delete takes a while, so keep it off the UI thread
+        on_deleted(&format!("Incinerating {}...", name));
+        let (tx, rx) = mpsc::channel();
+        let target = path.clone();
+        thread::spawn(move || {
+            let _ = tx.send(delete_entry(&target, mode));
+        });
+
+        let (window, path, name) = (window.clone(), path.clone(), name.clone());
+        let (state, on_deleted) = (state.clone(), on_deleted.clone());
+        timeout_add_local(Duration::from_millis(50), move || {
+            let result = match rx.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+                Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+            };
+
+            let failed = format!("Could not incinerate {}", name);
+            let outcome = match result {
+                DeleteResult::Trashed(item) => Some((
+                    format!("{} moved to the Trash - Ctrl+Z to bring it back", name),
+                    Some(item),
+                )),
+                DeleteResult::Success => Some((format!("{} reduced to ashes", name), None)),
+                DeleteResult::Incomplete(failures) => {
+                    let mode = state.borrow().size_mode;
+                    let freed = state.borrow_mut().prune_deleted(&path);
+                    let message = format!(
+                        "{} partly reduced to ashes - {} freed, {} left behind",
+                        name,
+                        mode.format(freed),
+                        plural(failures.len(), "item")
+                    );
+                    on_deleted(&message);
+                    let details = describe_failures(&failures);
+                    if failures.iter().any(|f| f.permission_denied) {
+                        let targets = vec![path.clone()];
+                        let (state, on_deleted) = (state.clone(), on_deleted.clone());
+                        show_denied_dialog(&window, &message, &details, targets, state, on_deleted);
+                    } else {
+                        show_error_dialog(&window, &message, &details);
                     }
-                    s.rebuild_segments();
-                    drop(s);
-                    drawing_area.queue_draw();
+                    None
                 }
                 DeleteResult::ProtectedPath => {
-                    eprintln!("Cannot delete protected path");
+                    let details = format!("{} is protected", path.display());
+                    show_error_dialog(&window, &failed, &details);
+                    None
                 }
                 DeleteResult::PermissionDenied(e) => {
-                    eprintln!("Permission denied: {}", e);
+                    let details = format!("Permission denied: {}", e);
+                    let targets = vec![path.clone()];
+                    let (state, on_deleted) = (state.clone(), on_deleted.clone());
+                    show_denied_dialog(&window, &failed, &details, targets, state, on_deleted);
+                    None
                 }
                 DeleteResult::Error(e) => {
-                    eprintln!("Delete error: {}", e);
+                    show_error_dialog(&window, &failed, &e);
+                    None
                 }
                 DeleteResult::NotFound => {
-                    eprintln!("Path not found");
+                    // Already gone, so the tree shouldn't show it either
+                    state.borrow_mut().remove_entry(&path);
+                    on_deleted(&format!("{} no longer exists", name));
+                    let details = format!("{} no longer exists", path.display());
+                    show_error_dialog(&window, &failed, &details);
+                    None
+                }
+            };
+
+            if let Some((message, trashed)) = outcome {
+                // Update tree, keeping trashed subtrees around for undo
+                let mut s = state.borrow_mut();
+                let removed = s.remove_entry(&path);
+                if let (Some(item), Some(entry)) = (trashed, removed) {
+                    s.push_undo(item, entry);
                 }
+                drop(s);
+                on_deleted(&message);
             }
+
+            ControlFlow::Break
+        });
+    });
+
+    dialog.show();
+}
+
+/// Rescan one entry in the background and splice it into the tree
+///
+/// The result is dropped if a scan or snapshot replaces the tree meanwhile.
+fn refresh_subtree(path: PathBuf, state: Rc<RefCell<AppState>>, on_done: Rc<dyn Fn(&str)>) {
+    let (options, size_before, cancel) = {
+        let s = state.borrow();
+        // A tree still streaming in is about to change under the refresh
+        if s.scanning {
+            return;
+        }
+        let Some(entry) = s.find_entry(&path) else {
+            return;
+        };
+        // Rescanning would wander onto the filesystem the full scan stayed off
+        if entry.mount_point {
+            on_done(&format!(
+                "{} is a mount point that isn't scanned",
+                path.display()
+            ));
+            return;
+        }
+        let options = ScanOptions {
+            live: false,
+            ..s.scan_options.clone()
+        };
+        (
+            options,
+            entry.size_in(s.size_mode),
+            s.refresh_cancel.clone(),
+        )
+    };
+
+    on_done(&format!("Re-scorching {}...", path.display()));
+    let rx = scan_directory(path.clone(), options, cancel.clone());
+    timeout_add_local(Duration::from_millis(50), move || loop {
+        let progress = match rx.try_recv() {
+            Ok(progress) => progress,
+            Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+            Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+        };
+        if cancel.is_cancelled() {
+            return ControlFlow::Break;
+        }
+
+        match progress {
+            ScanProgress::Complete(tree) => {
+                let mut s = state.borrow_mut();
+                let mode = s.size_mode;
+                let size = tree.get(tree.root()).size_in(mode);
+                s.replace_subtree(tree);
+                drop(s);
+                on_done(&format!(
+                    "{} refreshed - {} ablaze (was {})",
+                    path.display(),
+                    mode.format(size),
+                    mode.format(size_before)
+                ));
+                return ControlFlow::Break;
+            }
+            ScanProgress::Error(e) => {
+                // Deleted since the last scan
+                if fs::symlink_metadata(&path).is_err() {
+                    state.borrow_mut().remove_entry(&path);
+                    on_done(&format!("{} no longer exists", path.display()));
+                } else {
+                    on_done(&format!("Error: {}", e));
+                }
+                return ControlFlow::Break;
+            }
+            _ => {}
         }
-        dialog.close();
     });
+}
+
+/// List who owns the bytes in the current view, by user and by group
+fn show_owners_dialog(window: &ApplicationWindow, state: &AppState) {
+    const LISTED: usize = 10;
+
+    let Some(tree) = &state.scan_root else {
+        return;
+    };
+    let Some(id) = tree.find(&state.view_root) else {
+        return;
+    };
+    let mode = state.size_mode;
+    let usage = owner_usage(tree, id, mode);
+    let total = tree.get(id).size_in(mode);
+
+    let describe = |name: String, usage: &Usage| {
+        let share = usage.size_in(mode) as f64 * 100.0 / total.max(1) as f64;
+        format!(
+            "{} - {} ({:.1}%), {}, {}",
+            name,
+            mode.format(usage.size_in(mode)),
+            share,
+            plural(usage.files as usize, "file"),
+            plural(usage.dirs as usize, "folder")
+        )
+    };
+    let mut lines = vec!["By user:".to_string()];
+    for (uid, usage) in usage.users.iter().take(LISTED) {
+        lines.push(describe(state.owner_names.user(*uid), usage));
+    }
+    if usage.users.len() > LISTED {
+        lines.push(format!("... and {} more", usage.users.len() - LISTED));
+    }
+    lines.push(String::new());
+    lines.push("By group:".to_string());
+    for (gid, usage) in usage.groups.iter().take(LISTED) {
+        lines.push(describe(state.owner_names.group(*gid), usage));
+    }
+    if usage.groups.len() > LISTED {
+        lines.push(format!("... and {} more", usage.groups.len() - LISTED));
+    }
+
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Info,
+        ButtonsType::Close,
+        format!("Who owns {}", state.view_root.display()),
+    );
+    dialog.set_secondary_text(Some(&lines.join("\n")));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
 
+/// Report a failed or partly failed action in a dialog
+fn show_error_dialog(window: &ApplicationWindow, summary: &str, details: &str) {
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Error,
+        ButtonsType::Close,
+        summary,
+    );
+    dialog.set_secondary_text(Some(details));
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.show();
+}
+
+/// Report a delete refused for lack of permission, offering to retry as root
+///
+/// Retrying deletes `targets` permanently through the privileged helper.
+fn show_denied_dialog(
+    window: &ApplicationWindow,
+    summary: &str,
+    details: &str,
+    targets: Vec<PathBuf>,
+    state: Rc<RefCell<AppState>>,
+    on_done: Rc<dyn Fn(&str)>,
+) {
+    if !helper_available() {
+        show_error_dialog(window, summary, details);
+        return;
+    }
+
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Error,
+        ButtonsType::None,
+        summary,
+    );
+    dialog.set_secondary_text(Some(&format!(
+        "{}\n\nRetrying as administrator deletes permanently - no Trash, no coming back!",
+        details
+    )));
+    dialog.add_buttons(&[
+        ("Close", ResponseType::Cancel),
+        ("Retry as administrator", ResponseType::Accept),
+    ]);
+
+    let window = window.clone();
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        if response == ResponseType::Accept {
+            retry_as_admin(&window, targets.clone(), state.clone(), on_done.clone());
+        }
+    });
     dialog.show();
 }
 
-fn remove_entry_from_tree(entry: &mut crate::model::DirEntry, target: &PathBuf) -> bool {
-    entry.children.retain(|child| &child.path != target);
+/// Delete `targets` as root off the UI thread, then update the tree and report
+fn retry_as_admin(
+    window: &ApplicationWindow,
+    targets: Vec<PathBuf>,
+    state: Rc<RefCell<AppState>>,
+    on_done: Rc<dyn Fn(&str)>,
+) {
+    // pkexec waits for the password prompt, so keep it off the UI thread
+    on_done("Waiting for administrator authorization...");
+    let total = targets.len();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let results: Vec<_> = targets
+            .into_iter()
+            .map(|path| {
+                let result = delete_as_admin(&path);
+                (path, result)
+            })
+            .collect();
+        let _ = tx.send(results);
+    });
+
+    let window = window.clone();
+    timeout_add_local(Duration::from_millis(50), move || {
+        let results = match rx.try_recv() {
+            Ok(results) => results,
+            Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+            Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+        };
+
+        let mut s = state.borrow_mut();
+        let mode = s.size_mode;
+        let mut burned = 0;
+        let mut freed = 0;
+        let mut problems = Vec::new();
+        for (path, result) in results {
+            match result {
+                DeleteResult::Success | DeleteResult::Trashed(_) => {
+                    burned += 1;
+                    freed += s.prune_deleted(&path);
+                }
+                DeleteResult::Incomplete(failures) => {
+                    freed += s.prune_deleted(&path);
+                    problems.push(describe_failures(&failures));
+                }
+                DeleteResult::NotFound => {
+                    s.remove_entry(&path);
+                    problems.push(format!("{}: no longer exists", path.display()));
+                }
+                DeleteResult::ProtectedPath => {
+                    problems.push(format!("{}: protected path", path.display()));
+                }
+                DeleteResult::PermissionDenied(e) | DeleteResult::Error(e) => {
+                    problems.push(format!("{}: {}", path.display(), e));
+                }
+            }
+        }
+        drop(s);
 
-    for child in &mut entry.children {
-        if remove_entry_from_tree(child, target) {
-            return true;
+        let summary = format!(
+            "Burned {} of {} as administrator - {} freed",
+            burned,
+            total,
+            mode.format(freed)
+        );
+        on_done(&summary);
+        if !problems.is_empty() {
+            show_error_dialog(&window, &summary, &problems.join("\n"));
         }
+        ControlFlow::Break
+    });
+}
+
+/// List what a permanent delete left behind, and why
+fn describe_failures(failures: &[RemoveFailure]) -> String {
+    const LISTED: usize = 12;
+
+    let mut lines: Vec<String> = failures
+        .iter()
+        .take(LISTED)
+        .map(|f| format!("{}: {}", f.path.display(), f.message))
+        .collect();
+    if failures.len() > LISTED {
+        lines.push(format!("... and {} more", failures.len() - LISTED));
     }
+    lines.join("\n")
+}
 
-    // Recalculate size
-    entry.size = entry.children.iter().map(|c| c.total_size()).sum();
+/// "1 item", "2 items"
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Show how much the marked entries would free, hiding the button when none are
+fn update_marked_button(btn: &Button, state: &AppState) {
+    let count = state.marked_roots().len();
+    btn.set_visible(count > 0);
+    btn.set_label(&format!(
+        "BURN {} marked ({})",
+        count,
+        state.size_mode.format(state.reclaimable())
+    ));
+}
+
+/// Confirm and delete every marked entry, then report how each one went
+fn show_batch_delete_dialog(
+    window: &ApplicationWindow,
+    state: Rc<RefCell<AppState>>,
+    on_done: impl Fn(&str) + 'static,
+) {
+    const LISTED: usize = 12;
+
+    let (targets, total, size_mode) = {
+        let s = state.borrow();
+        (s.marked_roots(), s.reclaimable(), s.size_mode)
+    };
+    if targets.is_empty() {
+        return;
+    }
+
+    let mut list: Vec<String> = targets
+        .iter()
+        .take(LISTED)
+        .map(|p| p.display().to_string())
+        .collect();
+    if targets.len() > LISTED {
+        list.push(format!("... and {} more", targets.len() - LISTED));
+    }
+    let message = format!(
+        "INCINERATE {} marked entries?\n\n{}\n\nTotal: {}\n\n\
+         BURN IT moves them to the Trash. Incinerate forever reduces them to ashes - no coming back!\n\
+         Export plan writes a script and JSON list to review and run later with scorch exec-plan.",
+        targets.len(),
+        list.join("\n"),
+        size_mode.format(total)
+    );
 
-    false
+    let dialog = MessageDialog::new(
+        Some(window),
+        gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+        MessageType::Warning,
+        ButtonsType::None,
+        &message,
+    );
+    dialog.add_buttons(&[
+        ("Spare", ResponseType::Cancel),
+        ("Export plan", ResponseType::Apply),
+        ("Incinerate forever", ResponseType::Reject),
+        ("BURN IT", ResponseType::Accept),
+    ]);
+
+    let window = window.clone();
+    let on_done: Rc<dyn Fn(&str)> = Rc::new(on_done);
+    dialog.connect_response(move |dialog, response| {
+        dialog.close();
+        let mode = match response {
+            ResponseType::Accept => DeleteMode::Trash,
+            ResponseType::Reject => DeleteMode::Permanent,
+            ResponseType::Apply => {
+                let (plannable, skipped): (Vec<&PathBuf>, Vec<&PathBuf>) =
+                    targets.iter().partition(|path| plan::is_plannable(path));
+                let entries = {
+                    let state = state.borrow();
+                    let Some(tree) = state.scan_root.as_ref() else {
+                        return;
+                    };
+                    plannable
+                        .into_iter()
+                        .filter_map(|path| tree.find(path))
+                        .map(|id| PlanEntry::from_scan(tree, id))
+                        .collect()
+                };
+                let skipped = skipped.into_iter().cloned().collect();
+                show_export_plan_dialog(&window, entries, skipped, on_done.clone());
+                return;
+            }
+            _ => return,
+        };
+
+        // Delete off the UI thread, one by one so a failure doesn't stop the rest
+        on_done(&format!("Incinerating {} marked entries...", targets.len()));
+        let (tx, rx) = mpsc::channel();
+        let worker_targets = targets.clone();
+        thread::spawn(move || {
+            for path in worker_targets {
+                let result = delete_entry(&path, mode);
+                if tx.send((path, result)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        let (window, state, on_done) = (window.clone(), state.clone(), on_done.clone());
+        let targets = targets.clone();
+        let mut lines = Vec::new();
+        let mut done = 0;
+        let mut burned = 0;
+        let mut freed = 0;
+        // Entries an administrator could still finish off
+        let mut denied = Vec::new();
+        timeout_add_local(Duration::from_millis(50), move || {
+            let before = done;
+            let finished = loop {
+                let (path, result) = match rx.try_recv() {
+                    Ok(item) => item,
+                    Err(mpsc::TryRecvError::Empty) => break false,
+                    Err(mpsc::TryRecvError::Disconnected) => break true,
+                };
+                done += 1;
+                let mut s = state.borrow_mut();
+                let size = s
+                    .find_entry(&path)
+                    .map_or(0, |entry| entry.size_in(s.size_mode));
+                let (outcome, trashed) = match result {
+                    DeleteResult::Trashed(item) => ("moved to the Trash".to_string(), Some(item)),
+                    DeleteResult::Success => ("reduced to ashes".to_string(), None),
+                    DeleteResult::Incomplete(failures) => {
+                        let pruned = s.prune_deleted(&path);
+                        freed += pruned;
+                        lines.push(format!(
+                            "✗ {} - {} freed, {} left behind:",
+                            path.display(),
+                            s.size_mode.format(pruned),
+                            plural(failures.len(), "item")
+                        ));
+                        for failure in failures.iter().take(3) {
+                            lines.push(format!(
+                                "    {}: {}",
+                                failure.path.display(),
+                                failure.message
+                            ));
+                        }
+                        if failures.len() > 3 {
+                            lines.push(format!("    ... and {} more", failures.len() - 3));
+                        }
+                        if failures.iter().any(|f| f.permission_denied) {
+                            denied.push(path.clone());
+                        }
+                        continue;
+                    }
+                    DeleteResult::ProtectedPath => {
+                        lines.push(format!("✗ {} - protected path", path.display()));
+                        continue;
+                    }
+                    DeleteResult::NotFound => {
+                        lines.push(format!("✗ {} - not found", path.display()));
+                        s.remove_entry(&path);
+                        continue;
+                    }
+                    DeleteResult::PermissionDenied(e) => {
+                        lines.push(format!("✗ {} - permission denied: {}", path.display(), e));
+                        denied.push(path.clone());
+                        continue;
+                    }
+                    DeleteResult::Error(e) => {
+                        lines.push(format!("✗ {} - {}", path.display(), e));
+                        continue;
+                    }
+                };
+
+                lines.push(format!("✓ {} - {}", path.display(), outcome));
+                burned += 1;
+                freed += size;
+                let removed = s.remove_entry(&path);
+                if let (Some(item), Some(entry)) = (trashed, removed) {
+                    s.push_undo(item, entry);
+                }
+            };
+            if !finished {
+                if done > before {
+                    on_done(&format!(
+                        "Incinerating marked entries - {} of {} done...",
+                        done,
+                        targets.len()
+                    ));
+                }
+                return ControlFlow::Continue;
+            }
+
+            let summary = format!(
+                "Burned {} of {} marked entries - {} freed",
+                burned,
+                targets.len(),
+                size_mode.format(freed)
+            );
+            on_done(&summary);
+
+            let results = MessageDialog::new(
+                Some(&window),
+                gtk4::DialogFlags::MODAL | gtk4::DialogFlags::DESTROY_WITH_PARENT,
+                if burned == targets.len() {
+                    MessageType::Info
+                } else {
+                    MessageType::Warning
+                },
+                ButtonsType::Close,
+                &summary,
+            );
+            if !denied.is_empty() && helper_available() {
+                lines.push(String::new());
+                lines.push(
+                    "Retrying as administrator deletes permanently - no Trash, no coming back!"
+                        .to_string(),
+                );
+                results.add_button(
+                    &format!("Retry {} as administrator", denied.len()),
+                    ResponseType::Accept,
+                );
+            }
+            results.set_secondary_text(Some(&lines.join("\n")));
+            let (window, state, on_done) = (window.clone(), state.clone(), on_done.clone());
+            let denied = std::mem::take(&mut denied);
+            results.connect_response(move |dialog, response| {
+                dialog.close();
+                if response == ResponseType::Accept {
+                    retry_as_admin(&window, denied.clone(), state.clone(), on_done.clone());
+                }
+            });
+            results.show();
+            ControlFlow::Break
+        });
+    });
+
+    dialog.show();
+}
+
+/// Write the marked entries out as a deletion plan instead of deleting them
+///
+/// `skipped` are marked entries a plan can't hold, listed once it's written.
+fn show_export_plan_dialog(
+    window: &ApplicationWindow,
+    entries: Vec<PlanEntry>,
+    skipped: Vec<PathBuf>,
+    on_done: Rc<dyn Fn(&str)>,
+) {
+    if entries.is_empty() {
+        show_skipped_plan_entries(window, &skipped);
+        return;
+    }
+
+    let dialog = FileChooserDialog::new(
+        Some("Export Deletion Plan"),
+        Some(window),
+        FileChooserAction::Save,
+        &[
+            ("Cancel", ResponseType::Cancel),
+            ("Export", ResponseType::Accept),
+        ],
+    );
+    dialog.set_current_name(&format!("scorch-plan.{}", plan::EXTENSION));
+
+    let window = window.clone();
+    dialog.connect_response(move |dialog, response| {
+        let file = dialog.file().and_then(|f| f.path());
+        dialog.close();
+        let file = match file {
+            Some(file) if response == ResponseType::Accept => file,
+            _ => return,
+        };
+
+        // Sizes and times come from the scan, so there's nothing slow left to do
+        let plan = Plan::new(entries.clone());
+        match plan::save(&file, &plan) {
+            Ok(script) => {
+                on_done(&format!(
+                    "Plan for {} entries ({}) written to {} and {}",
+                    plan.entries.len(),
+                    format_size(plan.total_size()),
+                    file.display(),
+                    script.display()
+                ));
+                show_skipped_plan_entries(&window, &skipped);
+            }
+            Err(e) => on_done(&format!("Error: {}", e)),
+        }
+    });
+
+    dialog.show();
+}
+
+/// List marked entries left out of a plan because their paths aren't UTF-8
+fn show_skipped_plan_entries(window: &ApplicationWindow, skipped: &[PathBuf]) {
+    if skipped.is_empty() {
+        return;
+    }
+    let list: Vec<String> = skipped.iter().map(|p| p.display().to_string()).collect();
+    let summary = format!(
+        "{} left out of the plan",
+        plural(skipped.len(), "marked path")
+    );
+    show_error_dialog(
+        window,
+        &summary,
+        &format!(
+            "A plan holds paths as UTF-8 text, and these aren't valid UTF-8:\n\n{}",
+            list.join("\n")
+        ),
+    );
 }
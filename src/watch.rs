@@ -0,0 +1,430 @@
+//! Following changes under the scan root with inotify
+//!
+//! Events are gathered for a moment and then turned into freshly scanned
+//! entries, so a busy build produces a few updates a second rather than one
+//! per write.
+
+use crate::scanner::{is_virtual_fs, scan_path, CancelToken, ScanOptions};
+use crate::tree::ScanTree;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long changes are gathered before they are sent on
+const BATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What every watched directory reports
+const WATCH_MASK: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MODIFY
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_ONLYDIR
+    | libc::IN_DONT_FOLLOW
+    | libc::IN_EXCL_UNLINK;
+
+/// A change to the scanned tree
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    /// An entry appeared or changed; carries it freshly scanned
+    Updated(ScanTree),
+    /// An entry is gone
+    Removed(PathBuf),
+    /// Some changes can't be followed, so the tree may drift until the next scan
+    Degraded(String),
+    /// Watching failed altogether
+    Error(String),
+}
+
+/// Start following changes under `root` in a background thread
+///
+/// Cancelling `stop` ends the watch.
+pub fn watch_tree(root: PathBuf, options: ScanOptions, stop: CancelToken) -> Receiver<WatchEvent> {
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let inotify = match Inotify::new() {
+            Ok(inotify) => inotify,
+            Err(e) => {
+                let _ = tx.send(WatchEvent::Error(format!(
+                    "Cannot watch for changes: {}",
+                    e
+                )));
+                return;
+            }
+        };
+        let mut watcher = Watcher::new(inotify, root.clone(), options, tx);
+        watcher.add_tree(&root);
+        watcher.run(&stop);
+    });
+
+    rx
+}
+
+/// State of a running watch
+struct Watcher {
+    inotify: Inotify,
+    /// The directory behind each watch descriptor
+    dirs: HashMap<i32, PathBuf>,
+    root: PathBuf,
+    options: ScanOptions,
+    /// Device of the root when staying on one filesystem
+    root_dev: Option<u64>,
+    tx: Sender<WatchEvent>,
+    /// Paths to look at again in the next batch
+    pending: BTreeSet<PathBuf>,
+    /// Directories that appeared since the last batch and need scanning
+    new_dirs: HashSet<PathBuf>,
+    /// Whether running out of watches has been reported
+    out_of_watches: bool,
+}
+
+impl Watcher {
+    fn new(inotify: Inotify, root: PathBuf, options: ScanOptions, tx: Sender<WatchEvent>) -> Self {
+        let root_dev = if options.one_file_system {
+            fs::metadata(&root).ok().map(|m| m.dev())
+        } else {
+            None
+        };
+        Self {
+            inotify,
+            dirs: HashMap::new(),
+            root,
+            options,
+            root_dev,
+            tx,
+            pending: BTreeSet::new(),
+            new_dirs: HashSet::new(),
+            out_of_watches: false,
+        }
+    }
+
+    fn run(&mut self, stop: &CancelToken) {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut last_batch = Instant::now();
+
+        while !stop.is_cancelled() {
+            let read = match self.inotify.wait(BATCH_INTERVAL) {
+                Ok(true) => self.inotify.read(&mut buffer),
+                Ok(false) => Ok(0),
+                Err(e) => Err(e),
+            };
+            match read {
+                Ok(len) => self.handle_events(&buffer[..len]),
+                // A signal arrived, or the events were already read
+                Err(e) if is_transient(&e) => {}
+                Err(e) => {
+                    let _ = self
+                        .tx
+                        .send(WatchEvent::Error(format!("Stopped watching: {}", e)));
+                    return;
+                }
+            }
+
+            if last_batch.elapsed() >= BATCH_INTERVAL {
+                last_batch = Instant::now();
+                if !self.flush() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Record every event in a buffer filled by `read`
+    fn handle_events(&mut self, mut bytes: &[u8]) {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+
+        while bytes.len() >= HEADER {
+            // SAFETY: the kernel only hands out whole events, and read_unaligned
+            // copes with the byte buffer's alignment
+            let event: libc::inotify_event =
+                unsafe { std::ptr::read_unaligned(bytes.as_ptr().cast()) };
+            let end = (HEADER + event.len as usize).min(bytes.len());
+            // The name is padded with NULs
+            let name = &bytes[HEADER..end];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            self.handle_event(event.wd, event.mask, OsStr::from_bytes(name));
+            bytes = &bytes[end..];
+        }
+    }
+
+    fn handle_event(&mut self, wd: i32, mask: u32, name: &OsStr) {
+        if mask & libc::IN_Q_OVERFLOW != 0 {
+            let _ = self.tx.send(WatchEvent::Degraded(
+                "Too many changes at once, some were missed - IGNITE to catch up".to_string(),
+            ));
+            return;
+        }
+        if mask & libc::IN_IGNORED != 0 {
+            self.dirs.remove(&wd);
+            return;
+        }
+        let Some(dir) = self.dirs.get(&wd) else {
+            return;
+        };
+        if name.is_empty() {
+            return;
+        }
+
+        let path = dir.join(name);
+        if mask & libc::IN_ISDIR != 0 {
+            if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                self.add_tree(&path);
+                self.new_dirs.insert(path.clone());
+            } else if mask & libc::IN_MOVED_FROM != 0 {
+                // Its watches would go on reporting under the old path
+                self.forget_tree(&path);
+            }
+        }
+        self.pending.insert(path);
+    }
+
+    /// Watch `path` and every directory beneath it
+    fn add_tree(&mut self, path: &Path) {
+        if is_virtual_fs(path) {
+            return;
+        }
+        match self.inotify.add_watch(path) {
+            Ok(wd) => {
+                self.dirs.insert(wd, path.to_path_buf());
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ENOSPC) => {
+                if !self.out_of_watches {
+                    self.out_of_watches = true;
+                    let _ = self.tx.send(WatchEvent::Degraded(
+                        "Out of inotify watches, some folders aren't followed \
+                         (raise fs.inotify.max_user_watches)"
+                            .to_string(),
+                    ));
+                }
+                return;
+            }
+            // Unreadable, or gone again already
+            Err(_) => return,
+        }
+
+        let Ok(items) = fs::read_dir(path) else {
+            return;
+        };
+        for item in items.flatten() {
+            let item_path = item.path();
+            let Ok(metadata) = fs::symlink_metadata(&item_path) else {
+                continue;
+            };
+            if metadata.is_dir() && self.root_dev.is_none_or(|dev| dev == metadata.dev()) {
+                self.add_tree(&item_path);
+            }
+        }
+    }
+
+    /// Stop watching `path` and everything beneath it
+    fn forget_tree(&mut self, path: &Path) {
+        let inotify = &self.inotify;
+        self.dirs.retain(|&wd, dir| {
+            let inside = dir.starts_with(path);
+            if inside {
+                inotify.remove_watch(wd);
+            }
+            !inside
+        });
+    }
+
+    /// Send what became of every pending path; false once nobody is listening
+    fn flush(&mut self) -> bool {
+        let pending = std::mem::take(&mut self.pending);
+        let new_dirs = std::mem::take(&mut self.new_dirs);
+        // Parents sort before their children, so each scan covers what follows
+        let mut scanned: Vec<&PathBuf> = Vec::new();
+
+        for path in &pending {
+            if scanned.iter().any(|dir| path.starts_with(dir)) {
+                continue;
+            }
+            let event = match fs::symlink_metadata(path) {
+                Ok(m) if m.is_file() || (m.is_dir() && new_dirs.contains(path)) => {
+                    if m.is_dir() {
+                        scanned.push(path);
+                    }
+                    match scan_path(path, &self.root, &self.options) {
+                        Ok(tree) => WatchEvent::Updated(tree),
+                        Err(_) => continue,
+                    }
+                }
+                // Changes inside a known directory report themselves
+                Ok(m) if m.is_dir() => continue,
+                // Symlinks and special files aren't part of the tree either
+                _ => WatchEvent::Removed(path.clone()),
+            };
+            if self.tx.send(event).is_err() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+    )
+}
+
+/// An inotify instance, closed on drop
+struct Inotify(OwnedFd);
+
+impl Inotify {
+    fn new() -> io::Result<Self> {
+        // SAFETY: inotify_init1 has no preconditions
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: fd was just created and nothing else owns it
+        Ok(Self(unsafe { OwnedFd::from_raw_fd(fd) }))
+    }
+
+    fn add_watch(&self, path: &Path) -> io::Result<i32> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: path is NUL-terminated and outlives the call
+        let wd = unsafe { libc::inotify_add_watch(self.0.as_raw_fd(), path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    fn remove_watch(&self, wd: i32) {
+        // SAFETY: a stale descriptor only makes the call fail with EINVAL
+        unsafe {
+            libc::inotify_rm_watch(self.0.as_raw_fd(), wd);
+        }
+    }
+
+    /// Wait up to `timeout` for events, returning whether any arrived
+    fn wait(&self, timeout: Duration) -> io::Result<bool> {
+        let mut pollfd = libc::pollfd {
+            fd: self.0.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: pollfd is a single valid entry for the duration of the call
+        let ready = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+        if ready < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ready > 0)
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: buffer is valid for writes of its whole length
+        let len =
+            unsafe { libc::read(self.0.as_raw_fd(), buffer.as_mut_ptr().cast(), buffer.len()) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(len as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// What an event says, with paths relative to `root`
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Seen {
+        Updated(PathBuf, u64),
+        Removed(PathBuf),
+    }
+
+    /// Take in every event queued so far, then send one batch
+    fn batch(watcher: &mut Watcher, rx: &Receiver<WatchEvent>, root: &Path) -> Vec<Seen> {
+        let mut buffer = vec![0u8; 64 * 1024];
+        while watcher.inotify.wait(Duration::ZERO).unwrap() {
+            let len = watcher.inotify.read(&mut buffer).unwrap();
+            watcher.handle_events(&buffer[..len]);
+        }
+        assert!(watcher.flush());
+
+        let relative = |path: &Path| path.strip_prefix(root).unwrap().to_path_buf();
+        let mut seen: Vec<_> = rx
+            .try_iter()
+            .map(|event| match event {
+                WatchEvent::Updated(tree) => {
+                    let size = tree.get(tree.root()).size;
+                    Seen::Updated(relative(tree.root_path()), size)
+                }
+                WatchEvent::Removed(path) => Seen::Removed(relative(&path)),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        seen.sort();
+        seen
+    }
+
+    fn watches(watcher: &Watcher, path: &Path) -> bool {
+        watcher.dirs.values().any(|dir| dir == path)
+    }
+
+    #[test]
+    fn batches_creates_growth_and_removals() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().to_path_buf();
+        let (tx, rx) = mpsc::channel();
+        let options = ScanOptions {
+            threads: 1,
+            ..Default::default()
+        };
+        let mut watcher = Watcher::new(Inotify::new().unwrap(), root.clone(), options, tx);
+        watcher.add_tree(&root);
+        assert!(watches(&watcher, &root));
+
+        // A new directory arrives scanned whole, and is watched from then on
+        fs::write(root.join("a.txt"), "x").unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/b.txt"), "yy").unwrap();
+        assert_eq!(
+            batch(&mut watcher, &rx, &root),
+            [
+                Seen::Updated("a.txt".into(), 1),
+                Seen::Updated("sub".into(), 2),
+            ]
+        );
+        assert!(watches(&watcher, &root.join("sub")));
+
+        // Several writes to one file make one update
+        fs::write(root.join("a.txt"), "xx").unwrap();
+        fs::write(root.join("a.txt"), "xxx").unwrap();
+        fs::write(root.join("sub/c.txt"), "zzzz").unwrap();
+        assert_eq!(
+            batch(&mut watcher, &rx, &root),
+            [
+                Seen::Updated("a.txt".into(), 3),
+                Seen::Updated("sub/c.txt".into(), 4),
+            ]
+        );
+
+        fs::remove_file(root.join("a.txt")).unwrap();
+        fs::remove_dir_all(root.join("sub")).unwrap();
+        assert_eq!(
+            batch(&mut watcher, &rx, &root),
+            [
+                Seen::Removed("a.txt".into()),
+                Seen::Removed("sub".into()),
+                Seen::Removed("sub/b.txt".into()),
+                Seen::Removed("sub/c.txt".into()),
+            ]
+        );
+        assert!(!watches(&watcher, &root.join("sub")));
+    }
+}